@@ -0,0 +1,295 @@
+use std::{
+    f64::consts::PI,
+    time::{Duration, Instant},
+};
+
+use rand_distr::{Distribution, Exp};
+use tokio::time::sleep_until;
+
+use crate::config::smarteness_settings::LoadProfile;
+
+// lowest rate used to compute inter-arrival times, it avoids waiting forever...
+const MIN_RATE: f64 = 0.01;
+
+// most tasks started by a single tick, a late tick does not start all missed tasks at once...
+const MAX_BATCH: usize = 10_000;
+
+/// schedules the intended start of each task following the load profile,
+/// so the target rate changes over time in a single process.
+pub struct LoadScheduler {
+    load_profile: LoadProfile,
+    tasks_per_sec: f64,
+    start: Instant,
+    next_start: Instant,
+}
+
+impl LoadScheduler {
+    pub fn new(load_profile: LoadProfile, tasks_per_sec: f64) -> Self {
+        let start = Instant::now();
+
+        LoadScheduler {
+            load_profile,
+            tasks_per_sec,
+            start,
+            next_start: start,
+        }
+    }
+
+    /// wait until the next task is due and fill intended_starts with all tasks due by now.
+    /// timer granularity is about 1ms, so high rates start a batch of tasks per tick.
+    pub async fn tick(&mut self, intended_starts: &mut Vec<Instant>) {
+        intended_starts.clear();
+
+        if self.next_start > Instant::now() {
+            sleep_until(self.next_start.into()).await;
+        }
+
+        // next start is computed from the intended start, so delays are not accumulated...
+        let now = Instant::now();
+        while self.next_start <= now && intended_starts.len() < MAX_BATCH {
+            intended_starts.push(self.next_start);
+
+            let rate = self.current_rate();
+            self.next_start += self.inter_arrival(rate);
+        }
+    }
+
+    /// target rate in tasks per second at the next intended start
+    pub fn current_rate(&self) -> f64 {
+        self.rate_at(self.next_start - self.start).max(MIN_RATE)
+    }
+
+    /// target rate in tasks per second after elapsed time
+    pub fn rate_at(&self, elapsed: Duration) -> f64 {
+        let elapsed_minutes = elapsed.as_secs_f64() / 60.0;
+
+        match self.load_profile {
+            LoadProfile::Constant | LoadProfile::Poisson => self.tasks_per_sec,
+            LoadProfile::Sinusoid {
+                amplitude,
+                period_minutes,
+            } => {
+                self.tasks_per_sec + amplitude * (2.0 * PI * elapsed_minutes / period_minutes).sin()
+            }
+            LoadProfile::FlashCrowd {
+                rnorm,
+                shock_level,
+                n,
+                start_minutes,
+            } => {
+                let rnorm = rnorm.unwrap_or(self.tasks_per_sec);
+                let rflash = rnorm * shock_level - rnorm;
+
+                let rampup = 1.0 / (1.0 + shock_level).log10();
+                let sustained = (1.0 + shock_level).log10();
+                let rampdown = n * (1.0 + shock_level).log10();
+
+                let t = elapsed_minutes - start_minutes.unwrap_or(0.0);
+                if t < 0.0 {
+                    rnorm
+                } else if t < rampup {
+                    rnorm + rflash * t / rampup
+                } else if t < rampup + sustained {
+                    rnorm + rflash
+                } else if t < rampup + sustained + rampdown {
+                    rnorm + rflash * (1.0 - (t - rampup - sustained) / rampdown)
+                } else {
+                    rnorm
+                }
+            }
+            LoadProfile::Step {
+                increment,
+                step_minutes,
+            } => self.tasks_per_sec + increment * (elapsed_minutes / step_minutes).floor(),
+            LoadProfile::Ramp {
+                target_rate,
+                ramp_minutes,
+            } => {
+                let progress = (elapsed_minutes / ramp_minutes).min(1.0);
+                self.tasks_per_sec + (target_rate - self.tasks_per_sec) * progress
+            }
+        }
+    }
+
+    fn inter_arrival(&self, rate: f64) -> Duration {
+        match self.load_profile {
+            LoadProfile::Poisson => {
+                // rate is greater than 0, so exp is always valid...
+                let exp = Exp::new(rate).unwrap();
+                Duration::from_secs_f64(exp.sample(&mut rand::rng()))
+            }
+            _ => Duration::from_secs_f64(1.0 / rate),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate_at_minutes(load_profile: LoadProfile, tasks_per_sec: f64, minutes: f64) -> f64 {
+        LoadScheduler::new(load_profile, tasks_per_sec)
+            .rate_at(Duration::from_secs_f64(minutes * 60.0))
+    }
+
+    fn assert_rate(rate: f64, expected: f64) {
+        assert!(
+            (rate - expected).abs() < 1e-6,
+            "rate {} expected {}",
+            rate,
+            expected
+        );
+    }
+
+    #[test]
+    fn constant_and_poisson_keep_tasks_per_sec() {
+        for minutes in [0.0, 1.5, 60.0] {
+            assert_rate(
+                rate_at_minutes(LoadProfile::Constant, 100.0, minutes),
+                100.0,
+            );
+            assert_rate(rate_at_minutes(LoadProfile::Poisson, 100.0, minutes), 100.0);
+        }
+    }
+
+    #[test]
+    fn sinusoid_oscillates_around_tasks_per_sec() {
+        let sinusoid = LoadProfile::Sinusoid {
+            amplitude: 50.0,
+            period_minutes: 4.0,
+        };
+
+        assert_rate(rate_at_minutes(sinusoid, 100.0, 0.0), 100.0);
+        assert_rate(rate_at_minutes(sinusoid, 100.0, 1.0), 150.0);
+        assert_rate(rate_at_minutes(sinusoid, 100.0, 2.0), 100.0);
+        assert_rate(rate_at_minutes(sinusoid, 100.0, 3.0), 50.0);
+        assert_rate(rate_at_minutes(sinusoid, 100.0, 5.0), 150.0);
+    }
+
+    #[test]
+    fn flash_crowd_ramps_up_sustains_and_ramps_down() {
+        let flash_crowd = LoadProfile::FlashCrowd {
+            rnorm: None,
+            shock_level: 9.0,
+            n: 2.0,
+            start_minutes: Some(1.0),
+        };
+        // log10(1 + 9) = 1, so ramp up takes 1 minute, sustained 1 minute and ramp down 2 minutes
+        let rate = |minutes| rate_at_minutes(flash_crowd, 100.0, minutes);
+
+        assert_rate(rate(0.5), 100.0);
+        assert_rate(rate(1.5), 500.0);
+        assert_rate(rate(2.5), 900.0);
+        assert_rate(rate(4.0), 500.0);
+        assert_rate(rate(5.5), 100.0);
+    }
+
+    #[test]
+    fn step_adds_increment_every_step() {
+        let step = LoadProfile::Step {
+            increment: 25.0,
+            step_minutes: 2.0,
+        };
+
+        assert_rate(rate_at_minutes(step, 100.0, 0.0), 100.0);
+        assert_rate(rate_at_minutes(step, 100.0, 1.9), 100.0);
+        assert_rate(rate_at_minutes(step, 100.0, 2.0), 125.0);
+        assert_rate(rate_at_minutes(step, 100.0, 7.0), 175.0);
+    }
+
+    #[test]
+    fn ramp_reaches_target_rate_and_stays_there() {
+        let ramp = LoadProfile::Ramp {
+            target_rate: 300.0,
+            ramp_minutes: 10.0,
+        };
+
+        assert_rate(rate_at_minutes(ramp, 100.0, 0.0), 100.0);
+        assert_rate(rate_at_minutes(ramp, 100.0, 5.0), 200.0);
+        assert_rate(rate_at_minutes(ramp, 100.0, 10.0), 300.0);
+        assert_rate(rate_at_minutes(ramp, 100.0, 30.0), 300.0);
+    }
+
+    // scheduler whose first task was due some time ago, so tick does not wait
+    fn late_scheduler(tasks_per_sec: f64, late: Duration) -> LoadScheduler {
+        let mut load_scheduler = LoadScheduler::new(LoadProfile::Constant, tasks_per_sec);
+        load_scheduler.start -= late;
+        load_scheduler.next_start = load_scheduler.start;
+        load_scheduler
+    }
+
+    #[tokio::test]
+    async fn tick_starts_all_due_tasks_in_one_batch() {
+        let mut load_scheduler = late_scheduler(1_000.0, Duration::from_millis(100));
+        let mut intended_starts = Vec::new();
+        load_scheduler.tick(&mut intended_starts).await;
+
+        // tasks due in the last 100ms at 1ms each, plus the ones due while tick runs
+        assert!(intended_starts.len() >= 100, "{}", intended_starts.len());
+        assert_eq!(intended_starts[0], load_scheduler.start);
+        assert!(
+            intended_starts
+                .windows(2)
+                .all(|pair| pair[1] - pair[0] == Duration::from_millis(1))
+        );
+        assert!(load_scheduler.next_start > *intended_starts.last().unwrap());
+    }
+
+    #[tokio::test]
+    async fn tick_batches_are_capped() {
+        let mut load_scheduler = late_scheduler(1_000.0, Duration::from_secs(60));
+        let mut intended_starts = Vec::new();
+
+        load_scheduler.tick(&mut intended_starts).await;
+        assert_eq!(intended_starts.len(), MAX_BATCH);
+
+        // missed tasks are started by the following ticks, from where the previous one stopped
+        let last_start = *intended_starts.last().unwrap();
+        load_scheduler.tick(&mut intended_starts).await;
+        assert_eq!(intended_starts.len(), MAX_BATCH);
+        assert_eq!(intended_starts[0] - last_start, Duration::from_millis(1));
+    }
+
+    #[tokio::test]
+    async fn tick_waits_for_the_next_task() {
+        let mut load_scheduler = LoadScheduler::new(LoadProfile::Constant, 50.0);
+        let mut intended_starts = Vec::new();
+
+        load_scheduler.tick(&mut intended_starts).await;
+        assert_eq!(intended_starts.len(), 1);
+
+        load_scheduler.tick(&mut intended_starts).await;
+        assert!(!intended_starts.is_empty());
+        assert!(Instant::now() >= intended_starts[0]);
+        assert_eq!(
+            intended_starts[0] - load_scheduler.start,
+            Duration::from_millis(20)
+        );
+    }
+
+    #[test]
+    fn current_rate_is_never_zero() {
+        let step_down = LoadProfile::Step {
+            increment: -200.0,
+            step_minutes: 1.0,
+        };
+        let mut load_scheduler = LoadScheduler::new(step_down, 100.0);
+        load_scheduler.next_start += Duration::from_secs(120);
+        assert_rate(load_scheduler.current_rate(), MIN_RATE);
+    }
+
+    #[test]
+    fn inter_arrival_matches_rate() {
+        let constant = LoadScheduler::new(LoadProfile::Constant, 100.0);
+        assert_eq!(constant.inter_arrival(200.0), Duration::from_millis(5));
+
+        // poisson inter-arrival times are random, but their mean is 1 / rate...
+        let poisson = LoadScheduler::new(LoadProfile::Poisson, 100.0);
+        let samples = 20_000;
+        let mean = (0..samples)
+            .map(|_| poisson.inter_arrival(100.0).as_secs_f64())
+            .sum::<f64>()
+            / samples as f64;
+        assert!((mean - 0.01).abs() < 0.001, "mean {}", mean);
+    }
+}
@@ -1,47 +1,158 @@
 use std::{
-    fs::{self, File},
-    path::Path,
-    sync::Arc,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
 };
 
 use chrono::Utc;
-use csv::Writer;
-use scylla::client::session::Session;
-use tokio::{
-    runtime::Runtime,
-    time::{self},
-};
+use scylla::{client::session::Session, observability::metrics::Metrics};
+use tokio::{net::TcpListener, runtime::Runtime, task::JoinHandle, time};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
-    config::smarteness_settings::SmartnessSettings, error::SmartnessError,
-    metrics::metrics_manager::MetricsManager,
+    config::smarteness_settings::SmartnessSettings,
+    csql::csql_operation::CsqlOperation,
+    error::SmartnessError,
+    metrics::{
+        metrics_error::MetricsErrorKind,
+        metrics_manager::{LatencyStats, MetricSnapshot, MetricsManager},
+        metrics_prometheus::PrometheusExporter,
+        metrics_rate::RateMeter,
+        metrics_sink::{self, MetricColumn, MetricType, MetricValue, MetricsSink},
+    },
 };
 
+// prefixes of response time columns, measured from the intended start of each request
+const RESPONSE_TIME_PREFIX: &str = "rt_";
+
+// prefixes of failed operations columns, measured as service time
+const FAILED_PREFIX: &str = "f_";
+
+// prefixes of response time columns of failed operations
+const FAILED_RESPONSE_TIME_PREFIX: &str = "f_rt_";
+
+// metrics of the ScyllaDB session, rates are floats and the other ones are counters
+const SESSION_COLUMNS: [(&str, MetricType); 13] = [
+    ("queries_num", MetricType::Int),
+    ("queries_requested", MetricType::Int),
+    ("errors_occurred", MetricType::Int),
+    ("iter_errors_occurred", MetricType::Int),
+    ("average_latency", MetricType::Int),
+    ("99_9_latency_percentile", MetricType::Int),
+    ("mean_rate", MetricType::Float),
+    ("one_minute_rate", MetricType::Float),
+    ("five_minute_rate", MetricType::Float),
+    ("fifteen_minute_rate", MetricType::Float),
+    ("total_connections", MetricType::Int),
+    ("connection_timeouts", MetricType::Int),
+    ("requests_timeouts", MetricType::Int),
+];
+
+// latency columns written before percentiles
+const LATENCY_COLUMNS: [&str; 5] = ["min", "max", "mean", "std_dev", "median"];
+
+/// groups of latency stats of a MetricSnapshot, each one can be disabled by workload
+#[derive(Clone, Copy, PartialEq)]
+enum StatsGroup {
+    Overall,
+    Drained,
+    Windowed,
+    Interval,
+}
+
+impl StatsGroup {
+    /// prefix of its columns, after the prefix of response time or failed columns
+    fn prefix(&self) -> &'static str {
+        match self {
+            StatsGroup::Overall => "",
+            StatsGroup::Drained => "d_",
+            StatsGroup::Windowed => "w_",
+            StatsGroup::Interval => "i_",
+        }
+    }
+
+    fn stats<'a>(&self, snapshot: &'a MetricSnapshot) -> &'a LatencyStats {
+        match self {
+            StatsGroup::Overall => &snapshot.overall,
+            StatsGroup::Drained => &snapshot.drained,
+            StatsGroup::Windowed => &snapshot.windowed,
+            StatsGroup::Interval => &snapshot.interval,
+        }
+    }
+}
+
+/// prefix of all metrics files of a run
+pub fn metrics_file_name() -> String {
+    Utc::now().format("%Y%m%d_%H%M%S%3f").to_string()
+}
+
+/// runtime writing metrics rows of every operation into all sinks every metrics_interval_ms
+/// and serving them to Prometheus if metrics_prometheus_address is set
+pub struct MetricsRuntime {
+    runtime: Runtime,
+    stop_token: CancellationToken,
+    handle: JoinHandle<()>,
+}
+
+impl MetricsRuntime {
+    /// write the last rows, close all sinks and stop the runtime...
+    pub fn shutdown(self) {
+        self.stop_token.cancel();
+        if let Err(error) = self.runtime.block_on(self.handle) {
+            println!("Error when stop metrics task: {}", error);
+        }
+        self.runtime.shutdown_timeout(Duration::from_secs(2));
+    }
+}
+
 pub fn create_runtime(
     smartness_settings: &SmartnessSettings,
-    write_session: Arc<Session>,
-    write_mm: Arc<MetricsManager>,
-    read_session: Arc<Session>,
-    read_mm: Arc<MetricsManager>,
-) -> Result<Runtime, SmartnessError> {
-    let file_name = Utc::now().format("%Y%m%d_%H%M%S%3f").to_string();
-
-    println!(
-        "Metrics file: Write = {}_w.csv / Read = {}_r.csv ",
-        file_name, file_name
+    file_name: &str,
+    operations: &[Arc<CsqlOperation>],
+    current_phase: Arc<RwLock<String>>,
+    rate_meter: Arc<RateMeter>,
+) -> Result<MetricsRuntime, SmartnessError> {
+    let mut stats_groups = Vec::new();
+    if smartness_settings.metrics_overall_enabled.unwrap() {
+        stats_groups.push(StatsGroup::Overall);
+    }
+    if smartness_settings.metrics_drained_enabled.unwrap() {
+        stats_groups.push(StatsGroup::Drained);
+    }
+    if smartness_settings.metrics_windowed_enabled.unwrap() {
+        stats_groups.push(StatsGroup::Windowed);
+    }
+    if smartness_settings.metrics_interval_enabled.unwrap() {
+        stats_groups.push(StatsGroup::Interval);
+    }
+
+    let columns = metric_columns(
+        &stats_groups,
+        smartness_settings.metrics_percentiles.as_ref().unwrap(),
     );
 
-    let mut write_file = create_file(format!(
-        "{}/{}_w.csv",
-        &smartness_settings.metrics_dir.clone(),
-        file_name
-    ))?;
+    // one metrics file per operation and sink...
+    let mut metrics_sinks = Vec::with_capacity(operations.len());
+    for operation in operations {
+        let operation_file_path = format!(
+            "{}/{}_{}",
+            &smartness_settings.metrics_dir, file_name, operation.name
+        );
+
+        let mut operation_sinks = Vec::new();
+        for kind in smartness_settings.metrics_sinks.as_ref().unwrap() {
+            let (sink_file_path, sink) =
+                metrics_sink::create_sink(*kind, &operation_file_path, &columns)?;
 
-    let mut read_file = create_file(format!(
-        "{}/{}_r.csv",
-        &smartness_settings.metrics_dir.clone(),
-        file_name
-    ))?;
+            println!(
+                "Metrics file: Operation {} = {}",
+                operation.name, sink_file_path
+            );
+
+            operation_sinks.push(sink);
+        }
+
+        metrics_sinks.push((operation_sinks, Arc::clone(operation)));
+    }
 
     let metrics_runtime = tokio::runtime::Builder::new_multi_thread()
         .worker_threads(1)
@@ -50,149 +161,447 @@ pub fn create_runtime(
         .build()
         .map_err(SmartnessError::MetricsRuntimeBuildError)?;
 
-    metrics_runtime.spawn(async move {
-        let mut interval = time::interval(time::Duration::from_secs(1));
+    let stop_token = CancellationToken::new();
+    let stopped = stop_token.clone();
+
+    // Prometheus endpoint reads the same metrics on every scrape...
+    if let Some(address) = smartness_settings.metrics_prometheus_address.as_ref() {
+        let listener = metrics_runtime
+            .block_on(TcpListener::bind(address))
+            .map_err(SmartnessError::MetricsPrometheusBindError)?;
+
+        println!("Prometheus metrics: http://{}/metrics", address);
+
+        let exporter = Arc::new(PrometheusExporter {
+            operations: operations.to_vec(),
+            current_phase: current_phase.clone(),
+            rate_meter: rate_meter.clone(),
+            latency_unit: smartness_settings.metrics_latency_unit.unwrap(),
+        });
+        metrics_runtime.spawn(exporter.serve(listener, stop_token.clone()));
+    }
+
+    let metrics_interval = Duration::from_millis(smartness_settings.metrics_interval_ms.unwrap());
+    let handle = metrics_runtime.spawn(async move {
+        let mut interval = time::interval(metrics_interval);
+        let mut last_started_count = rate_meter.get_started_count();
+        let mut last_instant = Instant::now();
 
         loop {
-            write_metrics(&mut write_file, &write_session, write_mm.clone());
-            write_metrics(&mut read_file, &read_session, read_mm.clone());
+            // the last row is written when the runtime is stopped...
+            let stopping = tokio::select! {
+                _ = interval.tick() => false,
+                _ = stopped.cancelled() => true,
+            };
+
+            let phase = current_phase.read().unwrap().clone();
+
+            // achieved rate is measured over the real elapsed time since the last row...
+            let started_count = rate_meter.get_started_count();
+            let now = Instant::now();
+            let elapsed = now.duration_since(last_instant).as_secs_f64();
+            let achieved_rate = if elapsed > 0.0 {
+                (started_count - last_started_count) as f64 / elapsed
+            } else {
+                0.0
+            };
+            last_started_count = started_count;
+            last_instant = now;
+
+            let rates = (rate_meter.get_target_rate(), achieved_rate);
+            for (operation_sinks, operation) in metrics_sinks.iter_mut() {
+                write_metrics(
+                    operation_sinks,
+                    &stats_groups,
+                    &phase,
+                    rates,
+                    elapsed,
+                    &operation.session,
+                    operation.metrics_manager.clone(),
+                );
+            }
 
-            interval.tick().await;
+            if stopping {
+                break;
+            }
+        }
+
+        for (operation_sinks, _) in metrics_sinks {
+            for sink in operation_sinks {
+                if let Err(error) = sink.close() {
+                    println!("Error when close a metrics file: {}", error);
+                }
+            }
         }
     });
 
-    Ok(metrics_runtime)
+    Ok(MetricsRuntime {
+        runtime: metrics_runtime,
+        stop_token,
+        handle,
+    })
 }
 
-fn create_file(file_name: String) -> Result<Writer<File>, SmartnessError> {
-    let metrics_path = Path::new(&file_name);
-
-    if let Some(parent) = metrics_path.parent() {
-        fs::create_dir_all(parent).map_err(SmartnessError::MetricsParentPathCreateError)?;
-    }
-
-    let file = File::create(metrics_path).map_err(SmartnessError::MetricsFileCreateError)?;
-    let mut wtr = csv::Writer::from_writer(file);
-    wtr.write_record(&[
-        "timestamp",
-        "queries_num",
-        "queries_requested",
-        "errors_occurred",
-        "iter_errors_occurred",
-        "average_latency",
-        "99_9_latency_percentile",
-        "mean_rate",
-        "one_minute_rate",
-        "five_minute_rate",
-        "fifteen_minute_rate",
-        "total_connections",
-        "connection_timeouts",
-        "requests_timeouts",
-        "count",
-        "min",
-        "max",
-        "mean",
-        "std_dev",
-        "median",
-        "75th_percentile",
-        "95th_percentile",
-        "98th_percentile",
-        "99th_percentile",
-        "99_9th_percentile",
-        "d_min",
-        "d_max",
-        "d_mean",
-        "d_std_dev",
-        "d_median",
-        "d_75th_percentile",
-        "d_95th_percentile",
-        "d_98th_percentile",
-        "d_99th_percentile",
-        "d_99_9th_percentile",
-        "w_min",
-        "w_max",
-        "w_mean",
-        "w_std_dev",
-        "w_median",
-        "w_75th_percentile",
-        "w_95th_percentile",
-        "w_98th_percentile",
-        "w_99th_percentile",
-        "w_99_9th_percentile",
-    ])
-    .map_err(SmartnessError::MetricsFileWriteHeadersError)?;
-
-    Ok(wtr)
+/// columns of metrics rows, in the order values are pushed by metric_values...
+/// columns of the first metrics files come first, so they keep their positions.
+fn metric_columns(stats_groups: &[StatsGroup], percentiles: &[f64]) -> Vec<MetricColumn> {
+    let mut columns = vec![MetricColumn::new("timestamp", MetricType::Int)];
+
+    for (name, metric_type) in SESSION_COLUMNS {
+        columns.push(MetricColumn::new(name, metric_type));
+    }
+
+    columns.push(MetricColumn::new("count", MetricType::Int));
+    push_latency_columns(&mut columns, "", stats_groups, percentiles);
+
+    columns.push(MetricColumn::new("phase", MetricType::Text));
+    columns.push(MetricColumn::new("target_rate", MetricType::Float));
+    columns.push(MetricColumn::new("achieved_rate", MetricType::Float));
+
+    if stats_groups.contains(&StatsGroup::Interval) {
+        columns.push(MetricColumn::new("i_count", MetricType::Int));
+        columns.push(MetricColumn::new("i_throughput", MetricType::Float));
+    }
+    push_latency_columns(
+        &mut columns,
+        RESPONSE_TIME_PREFIX,
+        stats_groups,
+        percentiles,
+    );
+
+    columns.push(MetricColumn::new("f_count", MetricType::Int));
+    if stats_groups.contains(&StatsGroup::Interval) {
+        columns.push(MetricColumn::new("f_i_count", MetricType::Int));
+    }
+    for error_kind in MetricsErrorKind::ALL {
+        columns.push(MetricColumn::new(
+            format!("err_{}", error_kind.name()),
+            MetricType::Int,
+        ));
+    }
+    push_latency_columns(&mut columns, FAILED_PREFIX, stats_groups, percentiles);
+    push_latency_columns(
+        &mut columns,
+        FAILED_RESPONSE_TIME_PREFIX,
+        stats_groups,
+        percentiles,
+    );
+
+    columns.push(MetricColumn::new("dropped_count", MetricType::Int));
+    columns.push(MetricColumn::new("delayed_count", MetricType::Int));
+
+    columns
+}
+
+fn push_latency_columns(
+    columns: &mut Vec<MetricColumn>,
+    prefix: &str,
+    stats_groups: &[StatsGroup],
+    percentiles: &[f64],
+) {
+    for stats_group in stats_groups {
+        let prefix = format!("{}{}", prefix, stats_group.prefix());
+        for column in LATENCY_COLUMNS {
+            columns.push(MetricColumn::new(
+                format!("{}{}", prefix, column),
+                MetricType::Float,
+            ));
+        }
+
+        // 99.9 is written as 99_9th_percentile...
+        for percentile in percentiles {
+            columns.push(MetricColumn::new(
+                format!(
+                    "{}{}th_percentile",
+                    prefix,
+                    percentile.to_string().replace('.', "_")
+                ),
+                MetricType::Float,
+            ));
+        }
+    }
 }
 
 fn write_metrics(
-    csv_file: &mut Writer<File>,
+    metrics_sinks: &mut [Box<dyn MetricsSink>],
+    stats_groups: &[StatsGroup],
+    phase: &str,
+    rates: (f64, f64),
+    interval_secs: f64,
     session: &Session,
     metrics_manager: Arc<MetricsManager>,
 ) {
-    let metrics = session.get_metrics();
-
-    let mut metric_values = Vec::<String>::new();
-    metric_values.push(Utc::now().timestamp().to_string());
-    metric_values.push(metrics.get_queries_num().to_string());
-    metric_values.push(metrics.get_queries_iter_num().to_string());
-    metric_values.push(metrics.get_errors_num().to_string());
-    metric_values.push(metrics.get_errors_iter_num().to_string());
-    metric_values.push(metrics.get_latency_avg_ms().unwrap_or(0).to_string());
-    metric_values.push(
-        metrics
-            .get_latency_percentile_ms(99.9)
-            .unwrap_or(0)
-            .to_string(),
+    let metric_values = metric_values(
+        stats_groups,
+        phase,
+        rates,
+        interval_secs,
+        session_values(&session.get_metrics()),
+        &metrics_manager,
     );
-    metric_values.push(metrics.get_mean_rate().to_string());
-    metric_values.push(metrics.get_one_minute_rate().to_string());
-    metric_values.push(metrics.get_five_minute_rate().to_string());
-    metric_values.push(metrics.get_fifteen_minute_rate().to_string());
-    metric_values.push(metrics.get_total_connections().to_string());
-    metric_values.push(metrics.get_connection_timeouts().to_string());
-    metric_values.push(metrics.get_request_timeouts().to_string());
-
-    let snapshot = metrics_manager.generate_snapshot();
-    metric_values.push(snapshot.count.to_string());
-    metric_values.push(snapshot.min.to_string());
-    metric_values.push(snapshot.max.to_string());
-    metric_values.push(snapshot.mean.to_string());
-    metric_values.push(snapshot.std_dev.to_string());
-    metric_values.push(snapshot.median.to_string());
-    metric_values.push(snapshot.p_75th.to_string());
-    metric_values.push(snapshot.p_95th.to_string());
-    metric_values.push(snapshot.p_98th.to_string());
-    metric_values.push(snapshot.p_99th.to_string());
-    metric_values.push(snapshot.p_99_9th.to_string());
-
-    metric_values.push(snapshot.d_min.to_string());
-    metric_values.push(snapshot.d_max.to_string());
-    metric_values.push(snapshot.d_mean.to_string());
-    metric_values.push(snapshot.d_std_dev.to_string());
-    metric_values.push(snapshot.d_median.to_string());
-    metric_values.push(snapshot.d_p_75th.to_string());
-    metric_values.push(snapshot.d_p_95th.to_string());
-    metric_values.push(snapshot.d_p_98th.to_string());
-    metric_values.push(snapshot.d_p_99th.to_string());
-    metric_values.push(snapshot.d_p_99_9th.to_string());
-
-    metric_values.push(snapshot.w_min.to_string());
-    metric_values.push(snapshot.w_max.to_string());
-    metric_values.push(snapshot.w_mean.to_string());
-    metric_values.push(snapshot.w_std_dev.to_string());
-    metric_values.push(snapshot.w_median.to_string());
-    metric_values.push(snapshot.w_p_75th.to_string());
-    metric_values.push(snapshot.w_p_95th.to_string());
-    metric_values.push(snapshot.w_p_98th.to_string());
-    metric_values.push(snapshot.w_p_99th.to_string());
-    metric_values.push(snapshot.w_p_99_9th.to_string());
-
-    if let Err(error) = csv_file.write_record(metric_values) {
-        println!("Error when write a metrics record: {}", error);
-    }
-
-    if let Err(error) = csv_file.flush() {
-        println!("Error when flush a metrics record: {}", error);
+
+    for metrics_sink in metrics_sinks.iter_mut() {
+        if let Err(error) = metrics_sink.write_row(&metric_values) {
+            println!("Error when write a metrics record: {}", error);
+        }
+    }
+}
+
+/// values of SESSION_COLUMNS, in the same order
+fn session_values(metrics: &Metrics) -> Vec<MetricValue> {
+    vec![
+        MetricValue::Int(metrics.get_queries_num() as i64),
+        MetricValue::Int(metrics.get_queries_iter_num() as i64),
+        MetricValue::Int(metrics.get_errors_num() as i64),
+        MetricValue::Int(metrics.get_errors_iter_num() as i64),
+        MetricValue::Int(metrics.get_latency_avg_ms().unwrap_or(0) as i64),
+        MetricValue::Int(metrics.get_latency_percentile_ms(99.9).unwrap_or(0) as i64),
+        MetricValue::Float(metrics.get_mean_rate()),
+        MetricValue::Float(metrics.get_one_minute_rate()),
+        MetricValue::Float(metrics.get_five_minute_rate()),
+        MetricValue::Float(metrics.get_fifteen_minute_rate()),
+        MetricValue::Int(metrics.get_total_connections() as i64),
+        MetricValue::Int(metrics.get_connection_timeouts() as i64),
+        MetricValue::Int(metrics.get_request_timeouts() as i64),
+    ]
+}
+
+/// values of a metrics row, in the order of metric_columns
+fn metric_values(
+    stats_groups: &[StatsGroup],
+    phase: &str,
+    (target_rate, achieved_rate): (f64, f64),
+    interval_secs: f64,
+    session_values: Vec<MetricValue>,
+    metrics_manager: &MetricsManager,
+) -> Vec<MetricValue> {
+    let mut metric_values = vec![MetricValue::Int(Utc::now().timestamp())];
+    metric_values.extend(session_values);
+
+    let interval_enabled = stats_groups.contains(&StatsGroup::Interval);
+
+    let snapshot = metrics_manager.take_snapshot();
+    metric_values.push(MetricValue::Int(snapshot.count as i64));
+    push_snapshot(&mut metric_values, stats_groups, &snapshot);
+
+    metric_values.push(MetricValue::Text(phase.to_owned()));
+    metric_values.push(MetricValue::Float(target_rate));
+    metric_values.push(MetricValue::Float(achieved_rate));
+
+    if interval_enabled {
+        let interval_throughput = if interval_secs > 0.0 {
+            snapshot.interval_count as f64 / interval_secs
+        } else {
+            0.0
+        };
+        metric_values.push(MetricValue::Int(snapshot.interval_count as i64));
+        metric_values.push(MetricValue::Float(interval_throughput));
+    }
+
+    let response_snapshot = metrics_manager.take_response_snapshot();
+    push_snapshot(&mut metric_values, stats_groups, &response_snapshot);
+
+    let failed_snapshot = metrics_manager.take_failed_snapshot();
+    metric_values.push(MetricValue::Int(failed_snapshot.count as i64));
+    if interval_enabled {
+        metric_values.push(MetricValue::Int(failed_snapshot.interval_count as i64));
+    }
+    for error_count in metrics_manager.get_error_counts() {
+        metric_values.push(MetricValue::Int(error_count as i64));
+    }
+    push_snapshot(&mut metric_values, stats_groups, &failed_snapshot);
+
+    let failed_response_snapshot = metrics_manager.take_failed_response_snapshot();
+    push_snapshot(&mut metric_values, stats_groups, &failed_response_snapshot);
+
+    metric_values.push(MetricValue::Int(metrics_manager.get_dropped_count() as i64));
+    metric_values.push(MetricValue::Int(metrics_manager.get_delayed_count() as i64));
+
+    metric_values
+}
+
+fn push_snapshot(
+    metric_values: &mut Vec<MetricValue>,
+    stats_groups: &[StatsGroup],
+    snapshot: &MetricSnapshot,
+) {
+    for stats_group in stats_groups {
+        let stats = stats_group.stats(snapshot);
+        metric_values.extend(
+            [
+                stats.min,
+                stats.max,
+                stats.mean,
+                stats.std_dev,
+                stats.median,
+            ]
+            .map(MetricValue::Float),
+        );
+        metric_values.extend(
+            stats
+                .percentiles
+                .iter()
+                .map(|percentile| MetricValue::Float(percentile.value)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::config::smarteness_settings::test_settings;
+
+    const ALL_GROUPS: [StatsGroup; 4] = [
+        StatsGroup::Overall,
+        StatsGroup::Drained,
+        StatsGroup::Windowed,
+        StatsGroup::Interval,
+    ];
+
+    const PERCENTILES: [f64; 5] = [75.0, 95.0, 98.0, 99.0, 99.9];
+
+    fn column_names(columns: &[MetricColumn]) -> Vec<&str> {
+        columns.iter().map(|column| column.name.as_str()).collect()
+    }
+
+    #[test]
+    fn metric_columns_start_with_the_first_metrics_file_columns() {
+        let columns = metric_columns(&ALL_GROUPS, &PERCENTILES);
+
+        let mut first_columns = vec!["timestamp".to_owned()];
+        first_columns.extend(SESSION_COLUMNS.map(|(name, _)| name.to_owned()));
+        first_columns.push("count".to_owned());
+        for prefix in ["", "d_", "w_"] {
+            for column in [
+                "min",
+                "max",
+                "mean",
+                "std_dev",
+                "median",
+                "75th_percentile",
+                "95th_percentile",
+                "98th_percentile",
+                "99th_percentile",
+                "99_9th_percentile",
+            ] {
+                first_columns.push(format!("{}{}", prefix, column));
+            }
+        }
+
+        assert_eq!(first_columns.len(), 45);
+        assert_eq!(column_names(&columns)[..45], first_columns);
+    }
+
+    #[test]
+    fn metric_columns_append_new_columns_after_latencies() {
+        let columns = metric_columns(&ALL_GROUPS, &[99.0]);
+        let names = column_names(&columns);
+
+        let position = |name: &str| names.iter().position(|column| *column == name).unwrap();
+        assert_eq!(position("phase"), position("i_99th_percentile") + 1);
+        assert_eq!(position("target_rate"), position("phase") + 1);
+        assert_eq!(position("achieved_rate"), position("phase") + 2);
+        assert_eq!(position("i_count"), position("achieved_rate") + 1);
+        assert!(position("rt_min") < position("f_count"));
+        assert!(position("f_99th_percentile") < position("f_rt_min"));
+        assert_eq!(names[names.len() - 2..], ["dropped_count", "delayed_count"]);
+    }
+
+    #[test]
+    fn metric_values_match_metric_columns() {
+        let smartness_settings = test_settings("metric-values", json!({}));
+        let metrics_manager = MetricsManager::new(&smartness_settings);
+        metrics_manager.add_latency(Duration::from_millis(2), Duration::from_millis(3));
+        metrics_manager.add_error(
+            MetricsErrorKind::Timeout,
+            Duration::from_millis(4),
+            Duration::from_millis(5),
+        );
+
+        for stats_groups in [&ALL_GROUPS[..], &[StatsGroup::Overall]] {
+            let columns = metric_columns(stats_groups, &PERCENTILES);
+            let values = metric_values(
+                stats_groups,
+                "load",
+                (100.0, 99.5),
+                1.0,
+                SESSION_COLUMNS
+                    .iter()
+                    .map(|(_, metric_type)| match metric_type {
+                        MetricType::Float => MetricValue::Float(0.0),
+                        _ => MetricValue::Int(0),
+                    })
+                    .collect(),
+                &metrics_manager,
+            );
+
+            assert_eq!(values.len(), columns.len());
+            for (column, value) in columns.iter().zip(&values) {
+                assert!(
+                    matches!(
+                        (column.metric_type, value),
+                        (MetricType::Int, MetricValue::Int(_))
+                            | (MetricType::Float, MetricValue::Float(_))
+                            | (MetricType::Text, MetricValue::Text(_))
+                    ),
+                    "column {} has a value of another type",
+                    column.name
+                );
+            }
+
+            let value = |name: &str| {
+                let index = columns
+                    .iter()
+                    .position(|column| column.name == name)
+                    .unwrap();
+                values[index].to_string()
+            };
+            assert_eq!(value("count"), "1");
+            assert_eq!(value("phase"), "load");
+            assert_eq!(value("target_rate"), "100");
+            assert_eq!(value("achieved_rate"), "99.5");
+            assert_eq!(value("f_count"), "1");
+            assert_eq!(value("err_timeout"), "1");
+        }
+    }
+
+    #[test]
+    fn percentile_columns_replace_the_decimal_point() {
+        let columns = metric_columns(&[StatsGroup::Overall], &[50.0, 99.9, 99.99]);
+        let names = column_names(&columns);
+        for name in [
+            "50th_percentile",
+            "99_9th_percentile",
+            "99_99th_percentile",
+            "rt_99_9th_percentile",
+            "f_rt_99_99th_percentile",
+        ] {
+            assert!(names.contains(&name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn metric_columns_only_have_enabled_stats_groups() {
+        let columns = metric_columns(&[StatsGroup::Drained, StatsGroup::Interval], &[99.0]);
+        let names = column_names(&columns);
+
+        for name in [
+            "d_min",
+            "i_99th_percentile",
+            "rt_d_mean",
+            "f_i_max",
+            "i_count",
+        ] {
+            assert!(names.contains(&name), "{}", name);
+        }
+        for name in ["min", "w_min", "rt_min", "f_w_99th_percentile"] {
+            assert!(!names.contains(&name), "{}", name);
+        }
+
+        let without_interval = metric_columns(&[StatsGroup::Overall], &[99.0]);
+        let names = column_names(&without_interval);
+        assert!(!names.contains(&"i_count") && !names.contains(&"f_i_count"));
     }
 }
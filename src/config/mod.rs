@@ -1,3 +1,4 @@
+pub mod load_scheduler;
 pub mod metrics_runtime;
 pub mod process_runtime;
 pub mod smarteness_settings;
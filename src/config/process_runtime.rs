@@ -1,29 +1,55 @@
-use std::{fs::File, sync::Arc};
+use std::{
+    collections::HashMap,
+    fs::File,
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicI64, Ordering},
+    },
+    time::Instant,
+};
 
-use csv::{Reader, StringRecord};
+use csv::{Position, Reader, ReaderBuilder, StringRecord};
 use scylla::{client::session::Session, value::CqlValue};
 use tokio::{
     runtime::Runtime,
-    time::{Duration, interval, sleep},
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{Duration, sleep, timeout},
 };
-use tokio_util::task::TaskTracker;
-use uuid::Uuid;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 
 use crate::{
-    config::smarteness_settings::SmartnessSettings,
-    csql::csql_op::{self},
+    config::{
+        load_scheduler::LoadScheduler,
+        smarteness_settings::{
+            BindingSource, InFlightPolicy, OperationKind, PhaseSettings, RunMode, SmartnessSettings,
+        },
+    },
+    csql::{
+        csql_key_generator::CsqlKeyGenerator,
+        csql_key_pool::CsqlKeyPool,
+        csql_op::{self, CsqlStatement},
+        csql_operation::{CsqlOperation, CsqlOperationPicker},
+        csql_types::CsqlRowBinder,
+    },
     error::SmartnessError,
-    metrics::metrics_manager::MetricsManager,
+    metrics::{metrics_manager::MetricsManager, metrics_rate::RateMeter},
 };
 
+// wait of a closed loop client when there is no task to send...
+const NO_TASK_WAIT: Duration = Duration::from_millis(1);
+
 pub struct ProcessRuntime<'a> {
     pub runtime: Arc<Runtime>,
     pub dataset_file: Arc<File>,
     pub smartness_settings: &'a SmartnessSettings,
     pub write_session: Arc<Session>,
-    pub read_session: Arc<Session>,
-    pub write_metrics_manager: Arc<MetricsManager>,
-    pub read_metrics_manager: Arc<MetricsManager>,
+    pub operations: Vec<Arc<CsqlOperation>>,
+    /// name of the running phase, used to tag metrics rows
+    pub current_phase: Arc<RwLock<String>>,
+    /// target and achieved rates, reported by metrics rows
+    pub rate_meter: Arc<RateMeter>,
+    /// every spawned task, so requests in flight are drained on shutdown
+    pub tracker: TaskTracker,
 }
 
 impl<'a> ProcessRuntime<'a> {
@@ -44,276 +70,833 @@ impl<'a> ProcessRuntime<'a> {
 
         let (write_session, read_session) =
             runtime.block_on(csql_op::create_session(smartness_settings))?;
-
-        let write_mm = MetricsManager::new(smartness_settings);
-
-        let read_mm = MetricsManager::new(smartness_settings);
+        let write_session = Arc::new(write_session);
+        let read_session = Arc::new(read_session);
+
+        let write_consistency = smartness_settings
+            .write_consistency
+            .as_ref()
+            .map(|level| level.consistency);
+        let read_consistency = smartness_settings
+            .read_consistency
+            .as_ref()
+            .map(|level| level.consistency);
+        let serial_consistency = smartness_settings
+            .serial_consistency
+            .as_ref()
+            .map(|level| level.serial_consistency);
+
+        let key_generator = Arc::new(CsqlKeyGenerator::new(smartness_settings));
+        let operations_settings = smartness_settings.operations.as_ref().unwrap();
+
+        // each write operation bound to dataset has its own key pool...
+        // operations bound to key read from the pool of their keys_from, set by SmartnessSettings.
+        let key_pools: HashMap<&str, Arc<CsqlKeyPool>> = operations_settings
+            .iter()
+            .filter(|operation_settings| operation_settings.writes_keys())
+            .map(|operation_settings| {
+                (
+                    operation_settings.name.as_str(),
+                    Arc::new(CsqlKeyPool::new(smartness_settings)),
+                )
+            })
+            .collect();
+
+        // each operation uses the session and consistency of its kind and has its own metrics...
+        let operations = operations_settings
+            .iter()
+            .map(|operation_settings| {
+                let kind = operation_settings.kind();
+                let (session, consistency) = match kind {
+                    OperationKind::Write => (write_session.clone(), write_consistency),
+                    OperationKind::Read => (read_session.clone(), read_consistency),
+                };
+
+                Arc::new(CsqlOperation {
+                    name: operation_settings.name.clone(),
+                    kind,
+                    binding: operation_settings.binding(),
+                    weight: operation_settings.weight,
+                    session,
+                    statement: CsqlStatement::new(
+                        &operation_settings.script,
+                        consistency,
+                        serial_consistency,
+                    ),
+                    column_types: None,
+                    metrics_manager: Arc::new(MetricsManager::new(smartness_settings)),
+                    key_generator: key_generator.clone(),
+                    key_pool: match operation_settings.binding() {
+                        BindingSource::Key => operation_settings.keys_from.as_deref(),
+                        _ => Some(operation_settings.name.as_str()),
+                    }
+                    .and_then(|name| key_pools.get(name).cloned()),
+                })
+            })
+            .collect();
 
         Ok(Self {
             runtime: Arc::new(runtime),
             smartness_settings,
             dataset_file: Arc::new(dataset_file),
-            write_session: Arc::new(write_session),
-            read_session: Arc::new(read_session),
-            write_metrics_manager: Arc::new(write_mm),
-            read_metrics_manager: Arc::new(read_mm),
+            write_session,
+            operations,
+            current_phase: Arc::new(RwLock::new(String::new())),
+            rate_meter: Arc::new(RateMeter::default()),
+            tracker: TaskTracker::new(),
         })
     }
 
     pub fn handle_startup(&self) -> Result<(), SmartnessError> {
         // handle asynchronously startup_op...
         self.runtime.block_on(csql_op::startup_op(
-            &self.smartness_settings,
+            self.smartness_settings,
             self.write_session.clone(),
         ))?;
 
         Ok(())
     }
 
-    pub fn handle_warmup(&self, dataset_file: File) -> Result<(), SmartnessError> {
-        self.runtime.block_on(csql_op::warmup_op(
-            &self.smartness_settings,
+    pub fn handle_prepare(&mut self) -> Result<(), SmartnessError> {
+        // prepare statements once per session, after startup scripts were applied...
+        self.operations = self.runtime.block_on(csql_op::prepare_operations(
+            self.smartness_settings,
+            &self.operations,
+        ))?;
+
+        // load column types to bind dataset values of each operation...
+        self.operations = self.runtime.block_on(csql_op::load_column_types(
+            self.smartness_settings,
             self.write_session.clone(),
-            dataset_file,
+            &self.operations,
         ))?;
+
+        Ok(())
+    }
+
+    pub fn handle_warmup(&self, dataset_file: File) -> Result<(), SmartnessError> {
+        // warmup needs a write operation bound to dataset...
+        let warmup_operation = self.operations.iter().find(|operation| {
+            operation.kind == OperationKind::Write && operation.binding == BindingSource::Dataset
+        });
+
+        if let Some(operation) = warmup_operation {
+            self.runtime.block_on(csql_op::warmup_op(
+                self.smartness_settings,
+                operation,
+                dataset_file,
+            ))?;
+        } else if self.smartness_settings.warmup_enabled.unwrap_or(false) {
+            println!("Warmup skipped, there is no write operation bound to dataset.");
+        }
         Ok(())
     }
 
     pub fn start_runtime(&self) -> Result<(), SmartnessError> {
-        let dataset_file = Arc::clone(&self.dataset_file);
+        for operation in &self.operations {
+            println!("Operation {} | weight {}", operation.name, operation.weight);
+        }
 
-        let reads_interval = self.smartness_settings.reads_interval.unwrap();
-        let task_interval = self.smartness_settings.task_interval.unwrap() as u64;
-        let cols_qty = self.smartness_settings.cols_qty.unwrap();
+        let task_generator = Arc::new(Mutex::new(TaskGenerator::new(
+            self.smartness_settings,
+            &self.operations,
+            Arc::clone(&self.dataset_file),
+        )?));
+        let in_flight_limiter = self.smartness_settings.max_in_flight.map(|max_in_flight| {
+            InFlightLimiter::new(
+                max_in_flight,
+                self.smartness_settings.max_in_flight_policy.unwrap(),
+            )
+        });
 
-        println!(
-            "Reads Interval: {} | Task Interval (nanoseconds): {}",
-            reads_interval, task_interval
-        );
+        let runtime = Arc::clone(&self.runtime);
+        let phases = self.smartness_settings.phases.as_ref().unwrap();
+        let mode = self.smartness_settings.mode.unwrap();
+        let drain_timeout =
+            Duration::from_secs(self.smartness_settings.drain_timeout_secs.unwrap());
 
-        let write_op = Arc::new(
-            self.smartness_settings
-                .write_script
-                .as_ref()
-                .unwrap()
-                .clone(),
-        );
-        let read_op = Arc::new(
-            self.smartness_settings
-                .read_script
-                .as_ref()
-                .unwrap()
-                .clone(),
-        );
+        runtime.block_on(async {
+            let mut interrupt = Box::pin(tokio::signal::ctrl_c());
 
-        let runtime = Arc::clone(&self.runtime);
-        let write_session = Arc::clone(&self.write_session);
-        let write_mm = Arc::clone(&self.write_metrics_manager);
-        let read_session = Arc::clone(&self.read_session);
-        let read_mm = Arc::clone(&self.read_metrics_manager);
-
-        // running time has precendency over cycle...
-        if let Some(running_time) = &self.smartness_settings.running_time {
-            println!("Running time: {}", running_time);
-
-            let main_task = runtime.spawn(async move {
-                let mut count = 1;
-                let mut rdr = Reader::from_reader(dataset_file);
-                let mut task_interval = interval(Duration::from_nanos(task_interval));
-
-                {
-                    let empty_header = StringRecord::new();
-                    let _headers = rdr.headers().unwrap_or(&empty_header);
-                    // println!("CSV Headers: {:?}", headers);
+            // phases share sessions and prepared statements, only the schedule changes...
+            for phase in phases {
+                let weights = self.smartness_settings.phase_weights(phase);
+                match mode {
+                    RunMode::Open => println!(
+                        "Phase {} started | Tasks per second: {} | Load profile: {:?} | Weights: {:?}",
+                        phase.name,
+                        phase.tasks_per_sec.unwrap(),
+                        phase.load_profile.unwrap(),
+                        weights
+                    ),
+                    RunMode::Closed => println!(
+                        "Phase {} started | Clients: {} | Think time (ms): {} | Weights: {:?}",
+                        phase.name,
+                        self.smartness_settings.clients.unwrap(),
+                        self.smartness_settings.think_time_ms.unwrap(),
+                        weights
+                    ),
                 }
 
-                let mut iter = rdr.into_records();
-                let pos = iter.reader().position().clone();
-
-                loop {
-                    let write_op_aux = write_op.clone();
-                    let read_op_aux = read_op.clone();
-                    let write_session = write_session.clone();
-                    let write_mm = write_mm.clone();
-                    let read_session = read_session.clone();
-                    let read_mm = read_mm.clone();
-                    if let Some(record) = iter.next() {
-                        if let Ok(record) = record {
-                            if count % 1000 == 0 {
-                                println!("Quantity operations: {}", count);
-                            }
-
-                            if reads_interval <= 0 || count % reads_interval != 0 {
-                                let uuid = Uuid::new_v4();
-
-                                let mut cql_values = Vec::<CqlValue>::new();
-                                cql_values.push(CqlValue::Uuid(uuid));
-
-                                let mut count_col = 0;
-                                for value in record.iter() {
-                                    if cols_qty != -1 && count_col >= cols_qty {
-                                        break;
-                                    }
-
-                                    cql_values.push(CqlValue::Text(value.to_owned()));
-                                    count_col += 1;
-                                }
-
-                                tokio::spawn(async move {
-                                    if let Err(err) = csql_op::write_op(
-                                        write_session,
-                                        write_mm,
-                                        &write_op_aux,
-                                        cql_values,
-                                    )
-                                    .await
-                                    {
-                                        println!("Error: {:?}", err);
-                                    }
-                                });
-                            } else {
-                                tokio::spawn(async move {
-                                    if let Err(err) =
-                                        csql_op::read_op(read_session, read_mm, &read_op_aux).await
-                                    {
-                                        println!("Error: {:?}", err);
-                                    }
-                                });
-                            }
-
-                            count += 1;
+                *self.current_phase.write().unwrap() = phase.name.clone();
+                task_generator.lock().unwrap().set_weights(&weights);
+
+                let phase_run = async {
+                    match mode {
+                        RunMode::Open => {
+                            run_open_phase(
+                                phase,
+                                &task_generator,
+                                in_flight_limiter.as_ref(),
+                                &self.rate_meter,
+                                &self.tracker,
+                            )
+                            .await
                         }
-                    } else {
-                        if iter.reader_mut().seek(pos.clone()).is_ok() {
-                            iter = iter.into_reader().into_records();
+                        RunMode::Closed => {
+                            run_closed_phase(
+                                phase,
+                                self.smartness_settings,
+                                &task_generator,
+                                &self.rate_meter,
+                                &self.tracker,
+                            )
+                            .await
                         }
                     }
+                };
 
-                    task_interval.tick().await;
-                }
-            });
-
-            println!("Creating signal_runtime...");
-            let signal_runtime = tokio::runtime::Builder::new_multi_thread()
-                .worker_threads(1)
-                .thread_name("cassandra-signal-pool")
-                .enable_all()
-                .build()
-                .map_err(SmartnessError::MetricsRuntimeBuildError)?;
-
-            let mut interrupt = Box::pin(tokio::signal::ctrl_c());
-            signal_runtime.block_on(async {
                 tokio::select! {
                     _ = &mut interrupt => {
                         println!("It was interrupted....");
+                        break;
                     },
-                    _ = tokio::time::sleep(Duration::from_secs(*running_time as u64 * 60)) => {
-                        println!("We had a timeout....");
+                    _ = phase_run => {
+                        println!("Phase {} finished.", phase.name);
                     }
                 }
+            }
 
-                main_task.abort();
-                println!("ProcessRuntime main task aborted...");
-                sleep(Duration::from_secs(2)).await;
-            });
-        } else if let Some(cycles) = &self.smartness_settings.cycles {
-            println!("Cycles: {}", cycles);
+            // no task is spawned anymore, requests in flight are drained until drain timeout...
+            // tracker also holds closed loop clients, so requests in flight come from rate meter...
+            self.tracker.close();
+            println!(
+                "Draining {} requests in flight, drain timeout (seconds): {}",
+                self.rate_meter.get_in_flight_count(),
+                drain_timeout.as_secs()
+            );
+
+            drain_in_flight(&self.tracker, drain_timeout).await;
+        });
+
+        println!(
+            "Requests summary | completed: {} | failed: {} | abandoned: {}",
+            self.rate_meter.get_completed_count(),
+            self.rate_meter.get_failed_count(),
+            self.rate_meter.get_in_flight_count()
+        );
+
+        Ok(())
+    }
 
-            runtime.block_on(async {
-                let tracker = TaskTracker::new();
-                let mut task_interval = interval(Duration::from_nanos(task_interval));
+    pub fn shutdown(self) {
+        if let Ok(runtime) = Arc::try_unwrap(self.runtime) {
+            println!("Shutting down ProcessRuntime...");
+            runtime.shutdown_background();
+        } else {
+            println!("Error when try to shutting down ProcessRuntime...");
+        }
+    }
+}
 
-                let mut count = 0;
-                let mut rdr = Reader::from_reader(dataset_file);
+// run tasks of a phase in open loop until its running time is over or its cycles are done...
+// tasks start following the load profile, no matter how many are in flight unless there is a limiter.
+async fn run_open_phase(
+    phase: &PhaseSettings,
+    task_generator: &Mutex<TaskGenerator>,
+    in_flight_limiter: Option<&InFlightLimiter>,
+    rate_meter: &Arc<RateMeter>,
+    tracker: &TaskTracker,
+) {
+    let mut load_scheduler = LoadScheduler::new(
+        phase.load_profile.unwrap(),
+        phase.tasks_per_sec.unwrap() as f64,
+    );
+    let mut intended_starts = Vec::new();
+
+    // spawn the next task, false if it could not be created or it was dropped...
+    let spawn_next = async |intended_start: Instant| {
+        let Some((operation, values)) = task_generator.lock().unwrap().next_task() else {
+            return false;
+        };
+
+        let permit = match in_flight_limiter {
+            Some(in_flight_limiter) => {
+                match in_flight_limiter.acquire(&operation.metrics_manager).await {
+                    Some(permit) => Some(permit),
+                    None => return false,
+                }
+            }
+            None => None,
+        };
+
+        rate_meter.add_started();
+        let rate_meter = Arc::clone(rate_meter);
+        tracker.spawn(async move {
+            run_task(operation, values, intended_start, &rate_meter).await;
+            drop(permit);
+        });
+
+        true
+    };
+
+    // running time has precendency over cycle...
+    if let Some(running_time) = phase.running_time {
+        println!("Running time: {}", running_time);
+
+        let running_time = Duration::from_secs(running_time as u64 * 60);
+        let _ = timeout(running_time, async {
+            let mut count = 1;
+            loop {
+                // scheduled instants of due tasks, used to measure response time...
+                load_scheduler.tick(&mut intended_starts).await;
+                rate_meter.set_target_rate(load_scheduler.current_rate());
+
+                for intended_start in intended_starts.iter() {
+                    if spawn_next(*intended_start).await {
+                        if count % 1000 == 0 {
+                            println!("Quantity operations: {}", count);
+                        }
 
-                {
-                    let empty_header = StringRecord::new();
-                    let _headers = rdr.headers().unwrap_or(&empty_header);
-                    // println!("CSV Headers: {:?}", headers);
+                        count += 1;
+                    } else if task_generator.lock().unwrap().is_exhausted() {
+                        return;
+                    }
                 }
+            }
+        })
+        .await;
+    } else if let Some(cycles) = phase.cycles {
+        println!("Cycles: {}", cycles);
+
+        let mut count = 0;
+        'cycles: loop {
+            // scheduled instants of due tasks, used to measure response time...
+            load_scheduler.tick(&mut intended_starts).await;
+            rate_meter.set_target_rate(load_scheduler.current_rate());
+
+            for intended_start in intended_starts.iter() {
+                if count > cycles {
+                    break 'cycles;
+                }
+
+                if spawn_next(*intended_start).await {
+                    if count > 0 && count % 1000 == 0 {
+                        println!("Cycles count: {}", count);
+                    }
+
+                    count += 1;
+                } else if task_generator.lock().unwrap().is_exhausted() {
+                    break 'cycles;
+                }
+            }
+        }
+    }
 
-                let mut iter = rdr.into_records();
-                let pos = iter.reader().position().clone();
-                loop {
-                    println!("Cycles count: {}", count);
+    print_if_exhausted(task_generator);
+}
 
-                    if count > *cycles {
+// run tasks of a phase in closed loop until its running time is over or its cycles are done...
+// each client waits for its previous request and the think time before sending the next one.
+// when the phase is over, or its future is dropped, clients stop after their current request.
+async fn run_closed_phase(
+    phase: &PhaseSettings,
+    smartness_settings: &SmartnessSettings,
+    task_generator: &Arc<Mutex<TaskGenerator>>,
+    rate_meter: &Arc<RateMeter>,
+    tracker: &TaskTracker,
+) {
+    // clients have no target rate...
+    rate_meter.set_target_rate(0.0);
+
+    let think_time = Duration::from_millis(smartness_settings.think_time_ms.unwrap());
+    let cycles = phase.cycles;
+    let count = Arc::new(AtomicI64::new(0));
+
+    let stop = CancellationToken::new();
+    let _stop_guard = stop.clone().drop_guard();
+
+    let clients = TaskTracker::new();
+    for _ in 0..smartness_settings.clients.unwrap() {
+        let task_generator = Arc::clone(task_generator);
+        let count = Arc::clone(&count);
+        let rate_meter = Arc::clone(rate_meter);
+        let stop = stop.clone();
+
+        tracker.spawn(clients.track_future(async move {
+            while !stop.is_cancelled() {
+                let next_task = task_generator.lock().unwrap().next_task();
+                let Some((operation, values)) = next_task else {
+                    if task_generator.lock().unwrap().is_exhausted() {
                         break;
                     }
 
-                    let write_op_aux = write_op.clone();
-                    let read_op_aux = read_op.clone();
-                    let write_session = write_session.clone();
-                    let write_mm = write_mm.clone();
-                    let read_session = read_session.clone();
-                    let read_mm = read_mm.clone();
-                    if let Some(record) = iter.next() {
-                        if let Ok(record) = record {
-                            if reads_interval <= 0 || count % reads_interval != 0 {
-                                let uuid = Uuid::new_v4();
-
-                                let mut cql_values = Vec::<CqlValue>::new();
-                                cql_values.push(CqlValue::Uuid(uuid));
-
-                                let mut count_col = 0;
-                                for value in record.iter() {
-                                    if cols_qty != -1 && count_col >= cols_qty {
-                                        break;
-                                    }
-
-                                    cql_values.push(CqlValue::Text(value.to_owned()));
-                                    count_col += 1;
-                                }
-
-                                tokio::spawn(async move {
-                                    if let Err(err) = csql_op::write_op(
-                                        write_session,
-                                        write_mm,
-                                        &write_op_aux,
-                                        cql_values,
-                                    )
-                                    .await
-                                    {
-                                        println!("Error: {:?}", err);
-                                    }
-                                });
-                            } else {
-                                tokio::spawn(async move {
-                                    if let Err(err) =
-                                        csql_op::read_op(read_session, read_mm, &read_op_aux).await
-                                    {
-                                        println!("Error: {:?}", err);
-                                    }
-                                });
-                            }
-
-                            count += 1;
-                        }
-                    } else {
-                        if iter.reader_mut().seek(pos.clone()).is_ok() {
-                            iter = iter.into_reader().into_records();
-                        }
+                    // there is nothing to send yet, e.g. no key was written...
+                    sleep(NO_TASK_WAIT).await;
+                    continue;
+                };
+
+                let Some(cycle) = next_cycle(&count, cycles) else {
+                    break;
+                };
+
+                if cycle > 0 && cycle % 1000 == 0 {
+                    println!("Quantity operations: {}", cycle);
+                }
+
+                rate_meter.add_started();
+                // a client sends a request as soon as it is ready, so it is the intended start...
+                run_task(operation, values, Instant::now(), &rate_meter).await;
+
+                if !think_time.is_zero() {
+                    tokio::select! {
+                        _ = stop.cancelled() => break,
+                        _ = sleep(think_time) => {}
                     }
+                }
+            }
+        }));
+    }
+    clients.close();
+
+    // running time has precendency over cycle...
+    if let Some(running_time) = phase.running_time {
+        println!("Running time: {}", running_time);
+
+        let running_time = Duration::from_secs(running_time as u64 * 60);
+        let _ = timeout(running_time, clients.wait()).await;
+    } else {
+        println!("Cycles: {}", cycles.unwrap());
+
+        // clients stop by themselves once all cycles were sent...
+        clients.wait().await;
+    }
+
+    stop.cancel();
+    clients.wait().await;
+
+    print_if_exhausted(task_generator);
+}
+
+// a phase stops before its end if its operations could never produce a task...
+fn print_if_exhausted(task_generator: &Mutex<TaskGenerator>) {
+    if task_generator.lock().unwrap().is_exhausted() {
+        println!("There is no task to send, e.g. dataset is empty or no key could be written.");
+    }
+}
+
+/// wait until all tasks of a closed tracker finish, false if requests in flight were abandoned
+/// because drain timeout is over or it was interrupted again
+async fn drain_in_flight(tracker: &TaskTracker, drain_timeout: Duration) -> bool {
+    tokio::select! {
+        _ = tracker.wait() => {
+            println!("All requests in flight finished.");
+            true
+        },
+        _ = sleep(drain_timeout) => {
+            println!("Drain timeout, requests in flight will be abandoned.");
+            false
+        },
+        _ = tokio::signal::ctrl_c() => {
+            println!("It was interrupted again, requests in flight will be abandoned.");
+            false
+        }
+    }
+}
+
+// execute a task and count it as completed or failed...
+async fn run_task(
+    operation: Arc<CsqlOperation>,
+    values: Vec<CqlValue>,
+    intended_start: Instant,
+    rate_meter: &RateMeter,
+) {
+    match csql_op::execute_op(operation, values, intended_start).await {
+        Ok(()) => rate_meter.add_completed(),
+        Err(err) => {
+            rate_meter.add_failed();
+            println!("Error: {:?}", err);
+        }
+    }
+}
+
+/// index of the next cycle sent by a client of a closed phase, None once all cycles were sent
+fn next_cycle(count: &AtomicI64, cycles: Option<i64>) -> Option<i64> {
+    let cycle = count.fetch_add(1, Ordering::Relaxed);
+    match cycles {
+        Some(cycles) if cycle >= cycles => None,
+        _ => Some(cycle),
+    }
+}
+
+/// caps requests in flight of open loop, requests over the cap are dropped or delayed
+struct InFlightLimiter {
+    semaphore: Arc<Semaphore>,
+    policy: InFlightPolicy,
+}
+
+impl InFlightLimiter {
+    fn new(max_in_flight: usize, policy: InFlightPolicy) -> Self {
+        InFlightLimiter {
+            semaphore: Arc::new(Semaphore::new(max_in_flight)),
+            policy,
+        }
+    }
+
+    /// permit kept while a request is in flight, None if the request was dropped
+    async fn acquire(&self, metrics_manager: &MetricsManager) -> Option<OwnedSemaphorePermit> {
+        if let Ok(permit) = Arc::clone(&self.semaphore).try_acquire_owned() {
+            return Some(permit);
+        }
+
+        match self.policy {
+            InFlightPolicy::Drop => {
+                metrics_manager.add_dropped();
+                None
+            }
+            InFlightPolicy::Delay => {
+                // response time keeps counting from the intended start while it waits...
+                metrics_manager.add_delayed();
+                Arc::clone(&self.semaphore).acquire_owned().await.ok()
+            }
+        }
+    }
+}
+
+// creates tasks for operations picked by weight...
+// operations bound to dataset consume the next record, going back to the first one at the end.
+struct TaskGenerator {
+    operations: Vec<Arc<CsqlOperation>>,
+    picker: CsqlOperationPicker,
+    weights: Vec<u32>,
+    /// binder of each operation bound to dataset, in the same order of operations
+    binders: Vec<Option<CsqlRowBinder>>,
+    reader: Reader<Arc<File>>,
+    start_position: Position,
+    record: StringRecord,
+    /// true if a record was read since the dataset was started again from the first one
+    pass_has_record: bool,
+    /// true when a whole pass over the dataset had no record that could be read
+    dataset_exhausted: bool,
+}
+
+impl TaskGenerator {
+    fn new(
+        smartness_settings: &SmartnessSettings,
+        operations: &[Arc<CsqlOperation>],
+        dataset_file: Arc<File>,
+    ) -> Result<Self, SmartnessError> {
+        // records with missing fields are read, so binders report them...
+        let mut reader = ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(dataset_file);
+        let binders = {
+            let empty_header = StringRecord::new();
+            let headers = reader.headers().unwrap_or(&empty_header);
+            operations
+                .iter()
+                .map(|operation| {
+                    (operation.binding == BindingSource::Dataset)
+                        .then(|| {
+                            CsqlRowBinder::new(
+                                operation.column_types.as_ref(),
+                                headers,
+                                smartness_settings.cols_qty.unwrap(),
+                            )
+                        })
+                        .transpose()
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let start_position = reader.position().clone();
+        let weights: Vec<u32> = operations
+            .iter()
+            .map(|operation| operation.weight)
+            .collect();
+
+        Ok(TaskGenerator {
+            operations: operations.to_vec(),
+            picker: CsqlOperationPicker::new(&weights),
+            weights,
+            binders,
+            reader,
+            start_position,
+            record: StringRecord::new(),
+            pass_has_record: false,
+            dataset_exhausted: false,
+        })
+    }
+
+    /// weights in the same order of operations, picking starts again
+    fn set_weights(&mut self, weights: &[u32]) {
+        self.picker = CsqlOperationPicker::new(weights);
+        self.weights = weights.to_vec();
+    }
+
+    /// true if no operation of current weights could ever produce a task...
+    /// operations bound to key need a written key or their key writer that could write one.
+    fn is_exhausted(&self) -> bool {
+        let picked = || {
+            self.operations
+                .iter()
+                .zip(&self.weights)
+                .filter(|(_, weight)| **weight > 0)
+                .map(|(operation, _)| operation)
+        };
+        let writes_keys_into = |key_pool: &Arc<CsqlKeyPool>| {
+            !self.dataset_exhausted
+                && picked().any(|operation| {
+                    operation.writes_keys()
+                        && operation
+                            .key_pool
+                            .as_ref()
+                            .is_some_and(|written_pool| Arc::ptr_eq(written_pool, key_pool))
+                })
+        };
+
+        !picked().any(|operation| match operation.binding {
+            BindingSource::Dataset => !self.dataset_exhausted,
+            BindingSource::Key => operation
+                .key_pool
+                .as_ref()
+                .is_some_and(|key_pool| !key_pool.is_empty() || writes_keys_into(key_pool)),
+            BindingSource::None => true,
+        })
+    }
+
+    /// operation picked by weight and its values, None if values could not be bound
+    /// or there is no key yet
+    fn next_task(&mut self) -> Option<(Arc<CsqlOperation>, Vec<CqlValue>)> {
+        let index = self.picker.pick();
+        let operation = Arc::clone(&self.operations[index]);
+
+        let mut cql_values = Vec::new();
+        match operation.binding {
+            BindingSource::Dataset => {
+                if !self.next_record() {
+                    return None;
+                }
 
-                    task_interval.tick().await;
+                operation.key_generator.generate(&mut cql_values);
+                // operations bound to dataset always have a binder...
+                let binder = self.binders[index].as_ref().unwrap();
+                if let Err(err) = binder.bind(&self.record, &mut cql_values) {
+                    println!("Error: {:?}", err);
+                    return None;
+                }
+            }
+            BindingSource::Key => {
+                // there is nothing to read until a key is written...
+                cql_values = operation.key_pool.as_ref()?.pick()?;
+            }
+            BindingSource::None => {}
+        }
+
+        Some((operation, cql_values))
+    }
+
+    fn next_record(&mut self) -> bool {
+        match self.reader.read_record(&mut self.record) {
+            Ok(true) => {
+                self.pass_has_record = true;
+                true
+            }
+            Ok(false) => {
+                // end of dataset, start again from the first record...
+                // a whole pass without records means no record could ever be read.
+                if !self.pass_has_record || self.reader.seek(self.start_position.clone()).is_err() {
+                    self.dataset_exhausted = true;
+                    return false;
                 }
 
-                tracker.close();
-                tracker.wait().await;
+                self.pass_has_record = false;
+                self.next_record()
+            }
+            Err(err) => {
+                // a record that could not be read is skipped, next tasks read the following ones...
+                println!("Error when read a dataset record: {}", err);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::config::smarteness_settings::test_settings;
+
+    #[test]
+    fn next_cycle_sends_exactly_the_cycles_of_a_phase() {
+        let count = Arc::new(AtomicI64::new(0));
+        // clients claim cycles concurrently, as in a closed phase
+        let clients: Vec<_> = (0..4)
+            .map(|_| {
+                let count = Arc::clone(&count);
+                std::thread::spawn(move || {
+                    let mut sent = 0;
+                    while next_cycle(&count, Some(1_000)).is_some() {
+                        sent += 1;
+                    }
+                    sent
+                })
+            })
+            .collect();
+
+        let sent: i64 = clients
+            .into_iter()
+            .map(|client| client.join().unwrap())
+            .sum();
+        assert_eq!(sent, 1_000);
+        assert_eq!(next_cycle(&count, Some(1_000)), None);
+    }
+
+    #[test]
+    fn next_cycle_has_no_end_without_cycles() {
+        let count = AtomicI64::new(0);
+        assert_eq!(next_cycle(&count, Some(0)), None);
+        assert_eq!(next_cycle(&count, None), Some(1));
+        assert_eq!(next_cycle(&count, None), Some(2));
+    }
+
+    #[tokio::test]
+    async fn in_flight_limiter_drops_requests_over_the_cap() {
+        let metrics_manager = MetricsManager::new(&test_settings("limiter-drop", json!({})));
+        let in_flight_limiter = InFlightLimiter::new(2, InFlightPolicy::Drop);
+
+        let first = in_flight_limiter.acquire(&metrics_manager).await;
+        let second = in_flight_limiter.acquire(&metrics_manager).await;
+        assert!(first.is_some() && second.is_some());
+        assert!(in_flight_limiter.acquire(&metrics_manager).await.is_none());
+        assert_eq!(metrics_manager.get_dropped_count(), 1);
+
+        // a finished request frees its permit
+        drop(first);
+        assert!(in_flight_limiter.acquire(&metrics_manager).await.is_some());
+        assert_eq!(metrics_manager.get_dropped_count(), 1);
+        assert_eq!(metrics_manager.get_delayed_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn in_flight_limiter_delays_requests_over_the_cap() {
+        let metrics_manager = Arc::new(MetricsManager::new(&test_settings(
+            "limiter-delay",
+            json!({}),
+        )));
+        let in_flight_limiter = Arc::new(InFlightLimiter::new(1, InFlightPolicy::Delay));
+
+        let permit = in_flight_limiter.acquire(&metrics_manager).await.unwrap();
+        let delayed = tokio::spawn({
+            let in_flight_limiter = Arc::clone(&in_flight_limiter);
+            let metrics_manager = Arc::clone(&metrics_manager);
+            async move { in_flight_limiter.acquire(&metrics_manager).await.is_some() }
+        });
+
+        // the delayed request waits until the permit is released
+        while metrics_manager.get_delayed_count() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert!(!delayed.is_finished());
+        drop(permit);
+
+        assert!(delayed.await.unwrap());
+        assert_eq!(metrics_manager.get_delayed_count(), 1);
+        assert_eq!(metrics_manager.get_dropped_count(), 0);
+    }
 
-                sleep(Duration::from_secs(2)).await;
+    #[tokio::test]
+    async fn drain_in_flight_waits_for_requests_in_flight() {
+        let tracker = TaskTracker::new();
+        let rate_meter = Arc::new(RateMeter::default());
+        for _ in 0..3 {
+            rate_meter.add_started();
+            let rate_meter = Arc::clone(&rate_meter);
+            tracker.spawn(async move {
+                sleep(Duration::from_millis(10)).await;
+                rate_meter.add_completed();
             });
         }
+        tracker.close();
 
-        Ok(())
+        assert_eq!(rate_meter.get_in_flight_count(), 3);
+        assert!(drain_in_flight(&tracker, Duration::from_secs(10)).await);
+        assert_eq!(rate_meter.get_in_flight_count(), 0);
     }
 
-    pub fn shutdown(self) {
-        if let Ok(runtime) = Arc::try_unwrap(self.runtime) {
-            println!("Shutting down ProcessRuntime...");
-            runtime.shutdown_background();
-        } else {
-            println!("Error when try to shutting down ProcessRuntime...");
+    #[tokio::test]
+    async fn drain_in_flight_abandons_requests_after_drain_timeout() {
+        let tracker = TaskTracker::new();
+        let rate_meter = Arc::new(RateMeter::default());
+        rate_meter.add_started();
+        tracker.spawn(sleep(Duration::from_secs(60)));
+        tracker.close();
+
+        assert!(!drain_in_flight(&tracker, Duration::from_millis(10)).await);
+        assert_eq!(rate_meter.get_in_flight_count(), 1);
+    }
+
+    // generator without operations, so only its dataset records are read
+    fn task_generator(test_name: &str, dataset: &[u8]) -> TaskGenerator {
+        let path = env::temp_dir().join(format!("smartness-dataset-{}.csv", test_name));
+        std::fs::write(&path, dataset).unwrap();
+
+        let smartness_settings: SmartnessSettings = serde_json::from_value(json!({
+            "dataset_path": path.to_string_lossy(),
+            "metrics_dir": "metrics"
+        }))
+        .unwrap();
+        let dataset_file = Arc::new(File::open(&path).unwrap());
+        TaskGenerator::new(&smartness_settings, &[], dataset_file).unwrap()
+    }
+
+    #[test]
+    fn next_record_starts_again_from_the_first_record() {
+        let mut generator = task_generator("restart", b"id\n1\n2\n");
+        let mut ids = Vec::new();
+        for _ in 0..5 {
+            assert!(generator.next_record());
+            ids.push(generator.record[0].to_owned());
         }
+        assert_eq!(ids, ["1", "2", "1", "2", "1"]);
+        assert!(!generator.dataset_exhausted);
+    }
+
+    #[test]
+    fn next_record_exhausts_a_dataset_without_records() {
+        let mut generator = task_generator("empty", b"id\n");
+        assert!(!generator.next_record());
+        assert!(generator.dataset_exhausted);
+    }
+
+    #[test]
+    fn next_record_skips_invalid_records_after_a_restart() {
+        // the first record is not valid utf-8, so it fails on every pass
+        let mut generator = task_generator("invalid-first", b"id\n\xff\n2\n");
+
+        let read: Vec<bool> = (0..6).map(|_| generator.next_record()).collect();
+        assert_eq!(read, [false, true, false, true, false, true]);
+        assert!(!generator.dataset_exhausted);
+    }
+
+    #[test]
+    fn next_record_exhausts_a_dataset_without_valid_records() {
+        let mut generator = task_generator("invalid-all", b"id\n\xff\n");
+        assert!(!generator.next_record());
+        assert!(!generator.next_record());
+        assert!(generator.dataset_exhausted);
     }
 }
@@ -1,9 +1,313 @@
-use std::{fs::File, io::BufReader, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    fs::File,
+    io::{BufReader, ErrorKind},
+    net::SocketAddr,
+    path::Path,
+    time::Duration,
+};
 
+use scylla::statement::{Consistency, SerialConsistency};
 use serde::{Deserialize, Serialize};
 
 use crate::error::SmartnessError;
 
+// env vars with credentials, they override credentials file and workload
+const CASSANDRA_USERNAME_ENV: &str = "SMARTNESS_CASSANDRA_USERNAME";
+const CASSANDRA_PASSWORD_ENV: &str = "SMARTNESS_CASSANDRA_PASSWORD";
+
+// weights of a phase with reads_rate are scaled to this total, so shares keep 3 decimals
+const PHASE_WEIGHT_SCALE: f64 = 1000.0;
+
+/// unit of latencies written into metrics files
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LatencyUnit {
+    Ms,
+    Us,
+    Ns,
+}
+
+impl LatencyUnit {
+    /// value recorded by histograms, nanoseconds if unit is ns and microseconds otherwise
+    pub fn record_value(&self, latency: Duration) -> u64 {
+        match self {
+            LatencyUnit::Ns => latency.as_nanos() as u64,
+            LatencyUnit::Ms | LatencyUnit::Us => latency.as_micros() as u64,
+        }
+    }
+
+    /// convert a value recorded by histograms to this unit
+    pub fn output_value(&self, recorded_value: f64) -> f64 {
+        match self {
+            LatencyUnit::Ms => recorded_value / 1_000.0,
+            LatencyUnit::Us | LatencyUnit::Ns => recorded_value,
+        }
+    }
+
+    /// convert a value in this unit to seconds, the unit used by Prometheus
+    pub fn seconds_value(&self, output_value: f64) -> f64 {
+        match self {
+            LatencyUnit::Ms => output_value / 1_000.0,
+            LatencyUnit::Us => output_value / 1_000_000.0,
+            LatencyUnit::Ns => output_value / 1_000_000_000.0,
+        }
+    }
+}
+
+/// consistency level of a workload, it is parsed once from its name when the workload is read...
+/// the name is kept as written, so it is saved in reports and errors like the workload has it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub struct ConsistencyLevel {
+    name: String,
+    pub consistency: Consistency,
+}
+
+impl TryFrom<String> for ConsistencyLevel {
+    type Error = SmartnessError;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        let consistency = parse_consistency(&name)?;
+        Ok(ConsistencyLevel { name, consistency })
+    }
+}
+
+impl From<ConsistencyLevel> for String {
+    fn from(level: ConsistencyLevel) -> Self {
+        level.name
+    }
+}
+
+/// serial consistency level of a workload, parsed once like ConsistencyLevel
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub struct SerialConsistencyLevel {
+    name: String,
+    pub serial_consistency: SerialConsistency,
+}
+
+impl TryFrom<String> for SerialConsistencyLevel {
+    type Error = SmartnessError;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        let serial_consistency = parse_serial_consistency(&name)?;
+        Ok(SerialConsistencyLevel {
+            name,
+            serial_consistency,
+        })
+    }
+}
+
+impl From<SerialConsistencyLevel> for String {
+    fn from(level: SerialConsistencyLevel) -> Self {
+        level.name
+    }
+}
+
+/// output format of metrics files, each sink writes one file per operation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricsSinkKind {
+    Csv,
+    Jsonl,
+    Parquet,
+}
+
+impl MetricsSinkKind {
+    /// extension of files written by this sink
+    pub fn extension(&self) -> &'static str {
+        match self {
+            MetricsSinkKind::Csv => "csv",
+            MetricsSinkKind::Jsonl => "jsonl",
+            MetricsSinkKind::Parquet => "parquet",
+        }
+    }
+}
+
+/// kind of an operation, it selects the session and the consistency level used
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    Write,
+    Read,
+}
+
+/// source of values bound to an operation statement
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BindingSource {
+    /// a generated key followed by dataset columns
+    Dataset,
+    /// no bound values
+    None,
+    /// a key picked from keys already written by operations bound to dataset
+    Key,
+}
+
+/// distribution used to pick keys from the key pool
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyDistribution {
+    /// every key has the same probability
+    Uniform,
+    /// most recently written keys are more likely, following a zipfian distribution
+    Latest,
+    /// oldest written keys are more likely, following a zipfian distribution
+    Zipfian,
+}
+
+/// strategy used to generate keys of operations bound to dataset
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KeyGenerator {
+    /// random uuid
+    UuidV4,
+    /// time-ordered uuid
+    UuidV7,
+    /// timeuuid, bound as CQL timeuuid
+    TimeuuidV1,
+    /// bigint incremented by one from start, default 0
+    Sequential { start: Option<i64> },
+    /// bigint sequence spread over the whole bigint range, so keys are not ordered
+    HashedSequence { start: Option<i64> },
+    /// a bigint partition key picked from 0 to partitions - 1
+    /// followed by a bigint clustering key incremented by one
+    Composite { partitions: u64 },
+}
+
+/// shape of the target rate of tasks over time, rates are in tasks per second
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LoadProfile {
+    /// tasks_per_sec during all the test
+    Constant,
+    /// tasks_per_sec + amplitude * sin(2 * pi * t / period), amplitude must be lower than tasks_per_sec
+    Sinusoid { amplitude: f64, period_minutes: f64 },
+    /// tasks_per_sec on average, with exponential inter-arrival times
+    Poisson,
+    /// flash crowd from "Managing flash crowds on the Internet": normal rate rnorm (default
+    /// tasks_per_sec) grows to rnorm * shock_level during 1 / log10(1 + shock_level) minutes,
+    /// is sustained during log10(1 + shock_level) minutes and goes back to rnorm
+    /// during n * log10(1 + shock_level) minutes. The shock starts after start_minutes, default 0.
+    FlashCrowd {
+        rnorm: Option<f64>,
+        shock_level: f64,
+        n: f64,
+        start_minutes: Option<f64>,
+    },
+    /// tasks_per_sec increased by increment every step_minutes
+    Step { increment: f64, step_minutes: f64 },
+    /// tasks_per_sec linearly changed to target_rate during ramp_minutes, then target_rate
+    Ramp { target_rate: f64, ramp_minutes: f64 },
+}
+
+/// how tasks are started
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunMode {
+    /// tasks start following the load profile, no matter how many are in flight
+    Open,
+    /// each client sends a request after its previous one finished
+    Closed,
+}
+
+/// what open mode does with requests over max_in_flight
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InFlightPolicy {
+    /// request is not sent
+    Drop,
+    /// request waits until another one finishes
+    Delay,
+}
+
+/// how the driver picks the nodes that receive each request
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LoadBalancingPolicyKind {
+    /// replicas of the partition first, nodes of local_datacenter first if it is set
+    TokenAware,
+    /// round robin over nodes of local_datacenter, replicas are not preferred
+    DcAwareRoundRobin,
+    /// replicas in rack of local_datacenter first, then the other replicas of local_datacenter
+    RackAware { rack: String },
+    /// token aware, nodes slower than exclusion_threshold times the fastest one are avoided,
+    /// exclusion_threshold default 2
+    LatencyAware { exclusion_threshold: Option<f64> },
+}
+
+/// how credentials are sent to nodes that require authentication
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuthenticatorKind {
+    /// username and password, as PasswordAuthenticator of Cassandra and ScyllaDB expects
+    Password,
+    /// SASL PLAIN, authorization_id is the role to act as, if not set it is the username
+    SaslPlain { authorization_id: Option<String> },
+}
+
+/// credentials kept out of workload files, copy smartness-credentials.example.json
+/// to smartness-credentials.json next to the workload and set them there, git ignores it
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct CredentialsSettings {
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// client to node encryption, applied to write and read sessions
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TlsSettings {
+    /// PEM file with certificates of authorities trusted to sign node certificates
+    pub ca_cert_path: Option<String>,
+    /// PEM file with client certificate chain, set with client_key_path
+    pub client_cert_path: Option<String>,
+    /// PEM file with client private key, set with client_cert_path
+    pub client_key_path: Option<String>,
+    /// check that node certificates are valid for node addresses, default true
+    pub hostname_verification: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PhaseSettings {
+    /// name of the phase, it tags metrics rows written while it is active
+    pub name: String,
+    /// quantity of cycles of the phase
+    pub cycles: Option<i64>,
+    /// time in minutes of the phase, it has precedence over cycles
+    pub running_time: Option<i64>,
+    /// quantity of tasks that will start in a second, default tasks_per_sec
+    pub tasks_per_sec: Option<i32>,
+    /// percentage of read tasks, weights are scaled by operation kind to keep it
+    pub reads_rate: Option<f32>,
+    /// shape of the target rate during the phase, default load_profile
+    pub load_profile: Option<LoadProfile>,
+    /// weights by operation name, default weights of operations
+    pub operations: Option<HashMap<String, u32>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OperationSettings {
+    /// name of the operation, also used as suffix of its metrics file
+    pub name: String,
+    /// CQL statement of the operation
+    pub script: String,
+    /// weight used to pick this operation among all operations
+    pub weight: u32,
+    /// source of bound values, default dataset
+    pub binding: Option<BindingSource>,
+    /// write or read, default read if script is a SELECT and write otherwise
+    pub kind: Option<OperationKind>,
+    /// table in schema_keyspace used to load column types of this operation, default schema_table
+    pub schema_table: Option<String>,
+    /// column types of this operation by dataset column name, they override column_types
+    pub column_types: Option<HashMap<String, String>>,
+    /// write operation whose written keys are read by this operation bound to key,
+    /// default the only write operation bound to dataset
+    pub keys_from: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SmartnessSettings {
     /// quantity of workers to process tasks
@@ -18,8 +322,30 @@ pub struct SmartnessSettings {
     pub metrics_drain_interval_minutes: Option<i32>,
     /// window size in minutes for metrics with window
     pub metrics_window_size_minutes: Option<i32>,
-    /// window size for metrics with window
+    /// window size in seconds for metrics with window, computed from metrics_window_size_minutes
     pub metrics_window_size: Option<i32>,
+    /// significant figures kept by latency histograms, from 0 to 5, default 3
+    pub metrics_histogram_precision: Option<u8>,
+    /// unit of latencies in metrics files: ms, us or ns, default ms
+    pub metrics_latency_unit: Option<LatencyUnit>,
+    /// interval in milliseconds between metrics rows, default 1000
+    pub metrics_interval_ms: Option<u64>,
+    /// write stats over all latencies into metrics files, default true
+    pub metrics_overall_enabled: Option<bool>,
+    /// write stats over latencies since the last drain (d_ columns), default true
+    pub metrics_drained_enabled: Option<bool>,
+    /// write stats over latencies inside the window (w_ columns), default true
+    pub metrics_windowed_enabled: Option<bool>,
+    /// write stats over latencies since the previous metrics row (i_ columns), default true
+    pub metrics_interval_enabled: Option<bool>,
+    /// percentiles written after min, max, mean, std_dev and median,
+    /// default [75, 95, 98, 99, 99.9]
+    pub metrics_percentiles: Option<Vec<f64>>,
+    /// formats of metrics files written at the same time: csv, jsonl or parquet, default [csv]
+    pub metrics_sinks: Option<Vec<MetricsSinkKind>>,
+    /// address to serve metrics in Prometheus text format at /metrics, e.g. 0.0.0.0:9464,
+    /// disabled if not set
+    pub metrics_prometheus_address: Option<String>,
     /// quantity of cycles to run tests
     pub cycles: Option<i64>,
     /// time in minutes to run tests
@@ -28,12 +354,37 @@ pub struct SmartnessSettings {
     pub tasks_per_sec: Option<i32>,
     /// percentage of read tasks, default 0.1
     pub reads_rate: Option<f32>,
-    /// read task interval, computed using tasks_per_sec and reads_rate
-    pub reads_interval: Option<i64>,
-    /// task interval, computed using tasks_per_sec and 1000ms
-    pub task_interval: Option<i64>,
+    /// shape of the target rate over time, default constant
+    pub load_profile: Option<LoadProfile>,
+    /// open or closed, default open. Closed mode ignores tasks_per_sec and load_profile
+    pub mode: Option<RunMode>,
+    /// quantity of clients of closed mode
+    pub clients: Option<usize>,
+    /// time in milliseconds a client of closed mode waits between requests, default 0
+    pub think_time_ms: Option<u64>,
+    /// seconds to wait for requests in flight on shutdown or Ctrl-C before abandoning them, default 10
+    pub drain_timeout_secs: Option<u64>,
+    /// quantity of requests in flight of open mode, if not set there is no cap
+    pub max_in_flight: Option<usize>,
+    /// drop or delay requests over max_in_flight, default delay
+    pub max_in_flight_policy: Option<InFlightPolicy>,
+    /// phases run in order, each one overrides rate, read ratio, weights and duration.
+    /// if not set, a single phase named main uses cycles or running_time
+    pub phases: Option<Vec<PhaseSettings>>,
     /// quantity of columns that we will use, if -1 all columns will be used.
     pub cols_qty: Option<i64>,
+    /// host:port of nodes used to discover the cluster,
+    /// if not set, cassandra_host and cassandra_port will be used
+    pub cassandra_contact_points: Option<Vec<String>>,
+    /// datacenter of the load generator, nodes of other datacenters are only used
+    /// if permit_dc_failover is true
+    pub local_datacenter: Option<String>,
+    /// policy used to pick nodes, default token_aware
+    pub load_balancing_policy: Option<LoadBalancingPolicyKind>,
+    /// send requests to nodes of other datacenters when local ones are down, default false
+    pub permit_dc_failover: Option<bool>,
+    /// client to node encryption, plaintext if not set
+    pub tls: Option<TlsSettings>,
     /// host to connect to cassandra
     pub cassandra_host: Option<String>,
     /// port to connect to cassandra
@@ -42,10 +393,42 @@ pub struct SmartnessSettings {
     pub cassandra_username: Option<String>,
     /// password to connect to cassandra
     pub cassandra_password: Option<String>,
+    /// json file with username and password as smartness-credentials.example.json, read when
+    /// they are not set by SMARTNESS_CASSANDRA_USERNAME and SMARTNESS_CASSANDRA_PASSWORD,
+    /// relative paths start at the workload file directory and a missing file means no credentials
+    pub cassandra_credentials_path: Option<String>,
+    /// how credentials are sent to nodes, default password if credentials are set
+    pub cassandra_authenticator: Option<AuthenticatorKind>,
     /// script to use in write tasks
     pub write_script: Option<String>,
     /// script to use in read tasks
     pub read_script: Option<String>,
+    /// operations picked by weight, if not set, write_script and read_script will be used
+    /// as operations w and r with weights computed from tasks_per_sec and reads_rate
+    pub operations: Option<Vec<OperationSettings>>,
+    /// strategy used to generate keys of operations bound to dataset, default uuid_v4
+    pub key_generator: Option<KeyGenerator>,
+    /// quantity of written keys kept to be read by operations bound to key, default 100000
+    pub key_pool_size: Option<usize>,
+    /// distribution used to pick keys: uniform, latest or zipfian, default uniform
+    pub key_distribution: Option<KeyDistribution>,
+    /// exponent of latest and zipfian distributions, default 0.99
+    pub key_zipfian_exponent: Option<f64>,
+    /// consistency level of write tasks, e.g. ONE, QUORUM, LOCAL_QUORUM or ALL, default driver's
+    pub write_consistency: Option<ConsistencyLevel>,
+    /// consistency level of read tasks, e.g. ONE, QUORUM, LOCAL_QUORUM or ALL, default driver's
+    pub read_consistency: Option<ConsistencyLevel>,
+    /// serial consistency level of write and read tasks: SERIAL or LOCAL_SERIAL
+    pub serial_consistency: Option<SerialConsistencyLevel>,
+    /// if true, operations scripts will be prepared once per session, default true
+    pub prepared_statements_enabled: Option<bool>,
+    /// keyspace used to load column types from system_schema.columns
+    pub schema_keyspace: Option<String>,
+    /// table used to load column types from system_schema.columns
+    pub schema_table: Option<String>,
+    /// column types by dataset column name, it overrides types loaded from system_schema.
+    /// if neither is set, all dataset columns will be bound as text.
+    pub column_types: Option<HashMap<String, String>>,
     /// if true, we will use script to create schema, dropt table and create table
     pub startup_enabled: Option<bool>,
     ///script to create a schema on cassandra
@@ -60,6 +443,32 @@ pub struct SmartnessSettings {
     pub warmup_qty_ops: Option<i64>,
 }
 
+impl OperationSettings {
+    pub fn binding(&self) -> BindingSource {
+        self.binding.unwrap_or(BindingSource::Dataset)
+    }
+
+    /// write operations bound to dataset generate keys, so they have a key pool
+    pub fn writes_keys(&self) -> bool {
+        self.kind() == OperationKind::Write && self.binding() == BindingSource::Dataset
+    }
+
+    pub fn kind(&self) -> OperationKind {
+        self.kind.unwrap_or_else(|| {
+            if self
+                .script
+                .trim_start()
+                .to_uppercase()
+                .starts_with("SELECT")
+            {
+                OperationKind::Read
+            } else {
+                OperationKind::Write
+            }
+        })
+    }
+}
+
 // TODO change this name to smartnesssettings
 impl SmartnessSettings {
     pub fn new(workload_path: String, no_metrics: bool) -> Result<Self, SmartnessError> {
@@ -76,7 +485,10 @@ impl SmartnessSettings {
         let mut smartness_config: SmartnessSettings = serde_json::from_reader(reader)
             .map_err(SmartnessError::WorkloadFileDeserializationError)?;
 
-        if smartness_config.cycles.is_none() && smartness_config.running_time.is_none() {
+        if smartness_config.phases.is_none()
+            && smartness_config.cycles.is_none()
+            && smartness_config.running_time.is_none()
+        {
             return Err(SmartnessError::CyclesOrRunningTimeRequired);
         }
 
@@ -88,20 +500,152 @@ impl SmartnessSettings {
             smartness_config.metrics_window_size_minutes = Some(7);
         }
 
-        if smartness_config.cassandra_host.is_none() {
-            return Err(SmartnessError::CassandraHostRequired);
+        if smartness_config.metrics_window_size_minutes.unwrap() <= 0 {
+            return Err(SmartnessError::MetricsWindowSizeInvalid);
+        }
+
+        if smartness_config.metrics_histogram_precision.is_none() {
+            smartness_config.metrics_histogram_precision = Some(3);
+        }
+
+        if smartness_config.metrics_histogram_precision.unwrap() > 5 {
+            return Err(SmartnessError::MetricsHistogramPrecisionInvalid);
         }
 
-        if smartness_config.cassandra_port.is_none() {
-            return Err(SmartnessError::CassandraPortRequired);
+        if smartness_config.metrics_latency_unit.is_none() {
+            smartness_config.metrics_latency_unit = Some(LatencyUnit::Ms);
         }
 
-        if smartness_config.cassandra_username.is_none()
-            || smartness_config.cassandra_password.is_none()
+        if smartness_config.metrics_interval_ms.is_none() {
+            smartness_config.metrics_interval_ms = Some(1000);
+        }
+
+        if smartness_config.metrics_interval_ms.unwrap() == 0 {
+            return Err(SmartnessError::MetricsIntervalInvalid);
+        }
+
+        if smartness_config.metrics_overall_enabled.is_none() {
+            smartness_config.metrics_overall_enabled = Some(true);
+        }
+
+        if smartness_config.metrics_drained_enabled.is_none() {
+            smartness_config.metrics_drained_enabled = Some(true);
+        }
+
+        if smartness_config.metrics_windowed_enabled.is_none() {
+            smartness_config.metrics_windowed_enabled = Some(true);
+        }
+
+        if smartness_config.metrics_interval_enabled.is_none() {
+            smartness_config.metrics_interval_enabled = Some(true);
+        }
+
+        if smartness_config.metrics_percentiles.is_none() {
+            smartness_config.metrics_percentiles = Some(vec![75.0, 95.0, 98.0, 99.0, 99.9]);
+        }
+
+        let metrics_percentiles = smartness_config.metrics_percentiles.as_ref().unwrap();
+        for (index, percentile) in metrics_percentiles.iter().enumerate() {
+            // NaN is not greater than 0, so it is also rejected...
+            if !(*percentile > 0.0 && *percentile <= 100.0) {
+                return Err(SmartnessError::MetricsPercentileInvalid(*percentile));
+            }
+
+            if metrics_percentiles[..index].contains(percentile) {
+                return Err(SmartnessError::MetricsPercentileDuplicated(*percentile));
+            }
+        }
+
+        if smartness_config.metrics_sinks.is_none() {
+            smartness_config.metrics_sinks = Some(vec![MetricsSinkKind::Csv]);
+        }
+
+        let metrics_sinks = smartness_config.metrics_sinks.as_ref().unwrap();
+        if metrics_sinks.is_empty() {
+            return Err(SmartnessError::MetricsSinksRequired);
+        }
+
+        for (index, metrics_sink) in metrics_sinks.iter().enumerate() {
+            if metrics_sinks[..index].contains(metrics_sink) {
+                return Err(SmartnessError::MetricsSinkDuplicated(
+                    metrics_sink.extension().to_owned(),
+                ));
+            }
+        }
+
+        if let Some(address) = smartness_config.metrics_prometheus_address.as_ref()
+            && address.parse::<SocketAddr>().is_err()
         {
-            return Err(SmartnessError::CassandraUsernameAndPasswordAreRequired);
+            return Err(SmartnessError::MetricsPrometheusAddressInvalid(
+                address.clone(),
+            ));
         }
 
+        if smartness_config.cassandra_contact_points.is_none() {
+            if smartness_config.cassandra_host.is_none() {
+                return Err(SmartnessError::CassandraHostRequired);
+            }
+
+            if smartness_config.cassandra_port.is_none() {
+                return Err(SmartnessError::CassandraPortRequired);
+            }
+
+            smartness_config.cassandra_contact_points = Some(vec![format!(
+                "{}:{}",
+                smartness_config.cassandra_host.as_ref().unwrap(),
+                smartness_config.cassandra_port.unwrap()
+            )]);
+        }
+
+        if smartness_config
+            .cassandra_contact_points
+            .as_ref()
+            .unwrap()
+            .is_empty()
+        {
+            return Err(SmartnessError::CassandraContactPointsRequired);
+        }
+
+        if smartness_config.load_balancing_policy.is_none() {
+            smartness_config.load_balancing_policy = Some(LoadBalancingPolicyKind::TokenAware);
+        }
+
+        match smartness_config.load_balancing_policy.as_ref().unwrap() {
+            LoadBalancingPolicyKind::DcAwareRoundRobin
+            | LoadBalancingPolicyKind::RackAware { .. }
+                if smartness_config.local_datacenter.is_none() =>
+            {
+                return Err(SmartnessError::LocalDatacenterRequired);
+            }
+            LoadBalancingPolicyKind::LatencyAware {
+                exclusion_threshold: Some(exclusion_threshold),
+            } if exclusion_threshold.is_nan() || *exclusion_threshold < 1.0 => {
+                return Err(SmartnessError::LatencyAwareThresholdInvalid);
+            }
+            _ => {}
+        }
+
+        if smartness_config.permit_dc_failover.is_none() {
+            smartness_config.permit_dc_failover = Some(false);
+        }
+
+        if let Some(tls) = smartness_config.tls.as_mut() {
+            if tls.ca_cert_path.is_none() {
+                return Err(SmartnessError::TlsCaCertRequired);
+            }
+
+            if tls.client_cert_path.is_some() != tls.client_key_path.is_some() {
+                return Err(SmartnessError::TlsClientCertAndKeyRequired);
+            }
+
+            if tls.hostname_verification.is_none() {
+                tls.hostname_verification = Some(true);
+            }
+        }
+
+        smartness_config.resolve_credentials_path(workload_path);
+        smartness_config.resolve_credentials(|name| env::var(name).ok())?;
+
         if smartness_config.tasks_per_sec.is_none() {
             smartness_config.tasks_per_sec = Some(100);
         }
@@ -110,45 +654,134 @@ impl SmartnessSettings {
             smartness_config.reads_rate = Some(0.1);
         }
 
-        let reads_ops = (smartness_config.tasks_per_sec.unwrap() as f32
-            * smartness_config.reads_rate.unwrap())
-        .floor();
-
-        let writes_ops = smartness_config.tasks_per_sec.unwrap() as f32 - reads_ops;
+        if smartness_config.tasks_per_sec.unwrap() <= 0 {
+            return Err(SmartnessError::TasksPerSecInvalid);
+        }
 
-        let reads_interval = if reads_ops != 0.0 {
-            (writes_ops / reads_ops).floor()
-        } else {
-            reads_ops
-        };
+        if smartness_config.load_profile.is_none() {
+            smartness_config.load_profile = Some(LoadProfile::Constant);
+        }
 
-        smartness_config.reads_interval = Some(reads_interval as i64);
-        smartness_config.task_interval =
-            Some((1_000_000_000.0 / smartness_config.tasks_per_sec.unwrap() as f32).floor() as i64);
+        validate_load_profile(
+            smartness_config.load_profile.unwrap(),
+            smartness_config.tasks_per_sec.unwrap(),
+        )?;
 
-        smartness_config.metrics_window_size = Some(
-            smartness_config.metrics_window_size_minutes.unwrap() * 60, // * smartness_config.tasks_per_sec.unwrap(),
-        );
+        smartness_config.metrics_window_size =
+            Some(smartness_config.metrics_window_size_minutes.unwrap() * 60);
 
         if smartness_config.cols_qty.is_none() {
             smartness_config.cols_qty = Some(-1);
         }
 
-        if smartness_config.write_script.is_none() {
-            return Err(SmartnessError::WriteScriptRequired);
+        if smartness_config.operations.is_none() {
+            smartness_config.operations = Some(smartness_config.legacy_operations()?);
+        }
+
+        smartness_config.validate_operations()?;
+
+        if smartness_config.phases.is_none() {
+            smartness_config.phases = Some(vec![PhaseSettings {
+                name: "main".to_owned(),
+                cycles: smartness_config.cycles,
+                running_time: smartness_config.running_time,
+                tasks_per_sec: None,
+                reads_rate: None,
+                load_profile: None,
+                operations: None,
+            }]);
+        }
+
+        smartness_config.resolve_phases()?;
+
+        if smartness_config.mode.is_none() {
+            smartness_config.mode = Some(RunMode::Open);
+        }
+
+        if smartness_config.mode == Some(RunMode::Closed)
+            && smartness_config.clients.is_none_or(|clients| clients == 0)
+        {
+            return Err(SmartnessError::ClientsRequired);
+        }
+
+        if smartness_config.think_time_ms.is_none() {
+            smartness_config.think_time_ms = Some(0);
+        }
+
+        if smartness_config.drain_timeout_secs.is_none() {
+            smartness_config.drain_timeout_secs = Some(10);
+        }
+
+        if smartness_config.max_in_flight == Some(0) {
+            return Err(SmartnessError::MaxInFlightInvalid);
+        }
+
+        if smartness_config.max_in_flight_policy.is_none() {
+            smartness_config.max_in_flight_policy = Some(InFlightPolicy::Delay);
+        }
+
+        if smartness_config.key_generator.is_none() {
+            smartness_config.key_generator = Some(KeyGenerator::UuidV4);
+        }
+
+        if let Some(KeyGenerator::Composite { partitions: 0 }) = smartness_config.key_generator {
+            return Err(SmartnessError::KeyGeneratorPartitionsInvalid);
+        }
+
+        if smartness_config.key_pool_size.is_none() {
+            smartness_config.key_pool_size = Some(100_000);
+        }
+
+        if smartness_config.key_pool_size.unwrap() == 0 {
+            return Err(SmartnessError::KeyPoolSizeInvalid);
+        }
+
+        if smartness_config.key_distribution.is_none() {
+            smartness_config.key_distribution = Some(KeyDistribution::Uniform);
+        }
+
+        if smartness_config.key_zipfian_exponent.is_none() {
+            smartness_config.key_zipfian_exponent = Some(0.99);
         }
 
-        if smartness_config.read_script.is_none() {
-            return Err(SmartnessError::ReadScriptRequired);
+        if smartness_config.key_zipfian_exponent.unwrap() <= 0.0 {
+            return Err(SmartnessError::KeyZipfianExponentInvalid);
         }
 
-        if smartness_config.startup_enabled.is_some() && smartness_config.startup_enabled.unwrap() {
-            if smartness_config.startup_create_schema_script.is_none()
+        if let Some(write_consistency) = smartness_config.write_consistency.as_ref()
+            && matches!(
+                write_consistency.consistency,
+                Consistency::Serial | Consistency::LocalSerial
+            )
+        {
+            return Err(SmartnessError::WriteConsistencyNotAllowed(
+                write_consistency.name.clone(),
+            ));
+        }
+
+        if let Some(read_consistency) = smartness_config.read_consistency.as_ref()
+            && matches!(read_consistency.consistency, Consistency::Any)
+        {
+            return Err(SmartnessError::ReadConsistencyNotAllowed(
+                read_consistency.name.clone(),
+            ));
+        }
+
+        if smartness_config.prepared_statements_enabled.is_none() {
+            smartness_config.prepared_statements_enabled = Some(true);
+        }
+
+        if smartness_config.schema_keyspace.is_some() != smartness_config.schema_table.is_some() {
+            return Err(SmartnessError::SchemaKeyspaceAndTableRequired);
+        }
+
+        if smartness_config.startup_enabled.is_some()
+            && smartness_config.startup_enabled.unwrap()
+            && (smartness_config.startup_create_schema_script.is_none()
                 || smartness_config.startup_drop_table_script.is_none()
-                || smartness_config.startup_create_table_script.is_none()
-            {
-                return Err(SmartnessError::StartuptScriptsRequired);
-            }
+                || smartness_config.startup_create_table_script.is_none())
+        {
+            return Err(SmartnessError::StartuptScriptsRequired);
         }
 
         if smartness_config.warmup_enabled.is_some()
@@ -164,4 +797,1100 @@ impl SmartnessSettings {
 
         Ok(smartness_config)
     }
+
+    /// write_script and read_script as operations w and r, mixed using reads_rate
+    fn legacy_operations(&self) -> Result<Vec<OperationSettings>, SmartnessError> {
+        let write_script = self
+            .write_script
+            .clone()
+            .ok_or(SmartnessError::WriteScriptRequired)?;
+        let read_script = self
+            .read_script
+            .clone()
+            .ok_or(SmartnessError::ReadScriptRequired)?;
+
+        let reads_ops =
+            (self.tasks_per_sec.unwrap() as f32 * self.reads_rate.unwrap()).floor() as u32;
+        let writes_ops = (self.tasks_per_sec.unwrap() as u32).saturating_sub(reads_ops);
+
+        Ok(vec![
+            OperationSettings {
+                name: "w".to_owned(),
+                script: write_script,
+                weight: writes_ops,
+                binding: Some(BindingSource::Dataset),
+                kind: Some(OperationKind::Write),
+                schema_table: None,
+                column_types: None,
+                keys_from: None,
+            },
+            OperationSettings {
+                name: "r".to_owned(),
+                script: read_script,
+                weight: reads_ops,
+                binding: Some(BindingSource::None),
+                kind: Some(OperationKind::Read),
+                schema_table: None,
+                column_types: None,
+                keys_from: None,
+            },
+        ])
+    }
+
+    /// weights of operations during a phase, in the same order of operations
+    pub fn phase_weights(&self, phase: &PhaseSettings) -> Vec<u32> {
+        let operations = self.operations.as_ref().unwrap();
+        let weights: Vec<u32> = operations
+            .iter()
+            .map(|operation| {
+                phase
+                    .operations
+                    .as_ref()
+                    .and_then(|weights| weights.get(&operation.name).copied())
+                    .unwrap_or(operation.weight)
+            })
+            .collect();
+
+        let Some(reads_rate) = phase.reads_rate else {
+            return weights;
+        };
+
+        // weights are scaled so read operations get reads_rate of all tasks,
+        // if all weights of a kind are 0, its share is split equally...
+        let kind_totals = |kind: OperationKind| {
+            operations
+                .iter()
+                .zip(&weights)
+                .filter(|(operation, _)| operation.kind() == kind)
+                .fold((0.0, 0.0), |(total, count), (_, weight)| {
+                    (total + *weight as f64, count + 1.0)
+                })
+        };
+        let reads_totals = kind_totals(OperationKind::Read);
+        let writes_totals = kind_totals(OperationKind::Write);
+
+        operations
+            .iter()
+            .zip(&weights)
+            .map(|(operation, weight)| {
+                let (share, (total, count)) = match operation.kind() {
+                    OperationKind::Read => (reads_rate as f64, reads_totals),
+                    OperationKind::Write => (1.0 - reads_rate as f64, writes_totals),
+                };
+                let ratio = if total > 0.0 {
+                    *weight as f64 / total
+                } else {
+                    1.0 / count
+                };
+                (ratio * share * PHASE_WEIGHT_SCALE).round() as u32
+            })
+            .collect()
+    }
+
+    /// fill phases with defaults and validate them
+    fn resolve_phases(&mut self) -> Result<(), SmartnessError> {
+        let operations = self.operations.as_ref().unwrap();
+        let mut phases = self.phases.take().unwrap();
+        if phases.is_empty() {
+            return Err(SmartnessError::PhasesRequired);
+        }
+
+        let mut names = HashSet::new();
+        for phase in phases.iter_mut() {
+            if !is_valid_name(&phase.name) {
+                return Err(SmartnessError::PhaseNameInvalid(phase.name.clone()));
+            }
+
+            if !names.insert(phase.name.clone()) {
+                return Err(SmartnessError::PhaseNameDuplicated(phase.name.clone()));
+            }
+
+            if phase.cycles.is_none() && phase.running_time.is_none() {
+                return Err(SmartnessError::PhaseCyclesOrRunningTimeRequired(
+                    phase.name.clone(),
+                ));
+            }
+
+            if phase.tasks_per_sec.is_none() {
+                phase.tasks_per_sec = self.tasks_per_sec;
+            }
+
+            if phase.tasks_per_sec.unwrap() <= 0 {
+                return Err(SmartnessError::TasksPerSecInvalid);
+            }
+
+            if phase.load_profile.is_none() {
+                phase.load_profile = self.load_profile;
+            }
+
+            validate_load_profile(phase.load_profile.unwrap(), phase.tasks_per_sec.unwrap())?;
+
+            if phase
+                .reads_rate
+                .is_some_and(|reads_rate| !(0.0..=1.0).contains(&reads_rate))
+            {
+                return Err(SmartnessError::PhaseReadsRateInvalid(phase.name.clone()));
+            }
+
+            if let Some(weights) = phase.operations.as_ref() {
+                for operation_name in weights.keys() {
+                    if !operations
+                        .iter()
+                        .any(|operation| &operation.name == operation_name)
+                    {
+                        return Err(SmartnessError::PhaseOperationNotFound(
+                            phase.name.clone(),
+                            operation_name.clone(),
+                        ));
+                    }
+                }
+            }
+
+            if self.phase_weights(phase).iter().all(|weight| *weight == 0) {
+                return Err(SmartnessError::PhaseWeightRequired(phase.name.clone()));
+            }
+        }
+
+        self.phases = Some(phases);
+        Ok(())
+    }
+
+    /// credentials path is relative to the workload file, so workloads run from any directory
+    fn resolve_credentials_path(&mut self, workload_path: &Path) {
+        if let Some(credentials_path) = self.cassandra_credentials_path.as_mut()
+            && Path::new(credentials_path).is_relative()
+            && let Some(workload_dir) = workload_path.parent()
+        {
+            *credentials_path = workload_dir
+                .join(&credentials_path)
+                .to_string_lossy()
+                .into_owned();
+        }
+    }
+
+    /// credentials are taken from env vars first, then from credentials file and then workload...
+    /// empty env vars are ignored, so they do not override the other sources.
+    fn resolve_credentials(
+        &mut self,
+        env_var: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), SmartnessError> {
+        let env_var = |name| env_var(name).filter(|value: &String| !value.is_empty());
+        let env_username = env_var(CASSANDRA_USERNAME_ENV);
+        let env_password = env_var(CASSANDRA_PASSWORD_ENV);
+
+        // file is not needed when env vars set both credentials
+        let credentials = if env_username.is_some() && env_password.is_some() {
+            CredentialsSettings::default()
+        } else {
+            self.read_credentials_file()?
+        };
+
+        if let Some(username) = env_username {
+            self.cassandra_username = Some(username);
+        } else if credentials.username.is_some() {
+            self.cassandra_username = credentials.username;
+        }
+
+        if let Some(password) = env_password {
+            self.cassandra_password = Some(password);
+        } else if credentials.password.is_some() {
+            self.cassandra_password = credentials.password;
+        }
+
+        // auth is disabled when neither username nor password are set
+        if self.cassandra_username.is_some() != self.cassandra_password.is_some() {
+            return Err(SmartnessError::CassandraUsernameAndPasswordAreRequired);
+        }
+
+        if self.cassandra_username.is_some() {
+            if self.cassandra_authenticator.is_none() {
+                self.cassandra_authenticator = Some(AuthenticatorKind::Password);
+            }
+        } else if self.cassandra_authenticator.is_some() {
+            return Err(SmartnessError::CassandraAuthenticatorCredentialsRequired);
+        }
+
+        Ok(())
+    }
+
+    /// a missing credentials file has no credentials, as clusters without auth do not need it
+    fn read_credentials_file(&self) -> Result<CredentialsSettings, SmartnessError> {
+        let Some(credentials_path) = self.cassandra_credentials_path.as_ref() else {
+            return Ok(CredentialsSettings::default());
+        };
+
+        let credentials_file = match File::open(credentials_path) {
+            Ok(credentials_file) => credentials_file,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                return Ok(CredentialsSettings::default());
+            }
+            Err(err) => {
+                return Err(SmartnessError::CredentialsFileOpenError(
+                    credentials_path.clone(),
+                    err,
+                ));
+            }
+        };
+
+        serde_json::from_reader(BufReader::new(credentials_file)).map_err(|err| {
+            SmartnessError::CredentialsFileDeserializationError(credentials_path.clone(), err)
+        })
+    }
+
+    /// keys_from of operations bound to key defaults to the only write operation bound to dataset
+    fn validate_operations(&mut self) -> Result<(), SmartnessError> {
+        let operations = self.operations.as_ref().unwrap();
+        if operations.is_empty() {
+            return Err(SmartnessError::OperationsRequired);
+        }
+
+        let mut names = HashSet::new();
+        for operation in operations {
+            if !is_valid_name(&operation.name) {
+                return Err(SmartnessError::OperationNameInvalid(operation.name.clone()));
+            }
+
+            if !names.insert(operation.name.as_str()) {
+                return Err(SmartnessError::OperationNameDuplicated(
+                    operation.name.clone(),
+                ));
+            }
+
+            if operation.schema_table.is_some() && self.schema_keyspace.is_none() {
+                return Err(SmartnessError::OperationSchemaKeyspaceRequired(
+                    operation.name.clone(),
+                ));
+            }
+        }
+
+        if operations.iter().all(|operation| operation.weight == 0) {
+            return Err(SmartnessError::OperationsWeightRequired);
+        }
+
+        // keys are only written by write operations bound to dataset...
+        let key_writers: Vec<String> = operations
+            .iter()
+            .filter(|operation| operation.writes_keys())
+            .map(|operation| operation.name.clone())
+            .collect();
+        for operation in self.operations.as_mut().unwrap() {
+            if operation.binding() != BindingSource::Key {
+                continue;
+            }
+
+            match (&operation.keys_from, key_writers.as_slice()) {
+                (Some(keys_from), _) => {
+                    if !key_writers.contains(keys_from) {
+                        return Err(SmartnessError::OperationKeysFromInvalid(
+                            operation.name.clone(),
+                            keys_from.clone(),
+                        ));
+                    }
+                }
+                (None, []) => {
+                    return Err(SmartnessError::OperationKeyWriterRequired(
+                        operation.name.clone(),
+                    ));
+                }
+                (None, [key_writer]) => operation.keys_from = Some(key_writer.clone()),
+                (None, _) => {
+                    return Err(SmartnessError::OperationKeysFromRequired(
+                        operation.name.clone(),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// load profiles must keep rate greater than 0 and have durations greater than 0
+fn validate_load_profile(
+    load_profile: LoadProfile,
+    tasks_per_sec: i32,
+) -> Result<(), SmartnessError> {
+    let tasks_per_sec = tasks_per_sec as f64;
+
+    match load_profile {
+        LoadProfile::Constant | LoadProfile::Poisson => {}
+        LoadProfile::Sinusoid {
+            amplitude,
+            period_minutes,
+        } => {
+            if amplitude < 0.0 || amplitude >= tasks_per_sec {
+                return Err(SmartnessError::LoadProfileRateInvalid);
+            }
+            if period_minutes <= 0.0 {
+                return Err(SmartnessError::LoadProfileMinutesInvalid);
+            }
+        }
+        LoadProfile::FlashCrowd {
+            rnorm,
+            shock_level,
+            n,
+            start_minutes,
+        } => {
+            if rnorm.is_some_and(|rnorm| rnorm <= 0.0) || shock_level <= 1.0 {
+                return Err(SmartnessError::LoadProfileRateInvalid);
+            }
+            if n <= 0.0 || start_minutes.is_some_and(|start_minutes| start_minutes < 0.0) {
+                return Err(SmartnessError::LoadProfileMinutesInvalid);
+            }
+        }
+        LoadProfile::Step {
+            increment,
+            step_minutes,
+        } => {
+            if increment < 0.0 {
+                return Err(SmartnessError::LoadProfileRateInvalid);
+            }
+            if step_minutes <= 0.0 {
+                return Err(SmartnessError::LoadProfileMinutesInvalid);
+            }
+        }
+        LoadProfile::Ramp {
+            target_rate,
+            ramp_minutes,
+        } => {
+            if target_rate <= 0.0 {
+                return Err(SmartnessError::LoadProfileRateInvalid);
+            }
+            if ramp_minutes <= 0.0 {
+                return Err(SmartnessError::LoadProfileMinutesInvalid);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// names of operations and phases are used in metrics, so only letters, digits, _ and - are valid
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// parse a consistency level name like ONE, QUORUM or LOCAL_QUORUM, case insensitive
+fn parse_consistency(consistency: &str) -> Result<Consistency, SmartnessError> {
+    match consistency.trim().to_uppercase().as_str() {
+        "ANY" => Ok(Consistency::Any),
+        "ONE" => Ok(Consistency::One),
+        "TWO" => Ok(Consistency::Two),
+        "THREE" => Ok(Consistency::Three),
+        "QUORUM" => Ok(Consistency::Quorum),
+        "ALL" => Ok(Consistency::All),
+        "LOCAL_QUORUM" => Ok(Consistency::LocalQuorum),
+        "EACH_QUORUM" => Ok(Consistency::EachQuorum),
+        "LOCAL_ONE" => Ok(Consistency::LocalOne),
+        "SERIAL" => Ok(Consistency::Serial),
+        "LOCAL_SERIAL" => Ok(Consistency::LocalSerial),
+        _ => Err(SmartnessError::ConsistencyInvalid(consistency.to_owned())),
+    }
+}
+
+/// parse a serial consistency level name: SERIAL or LOCAL_SERIAL, case insensitive
+fn parse_serial_consistency(serial_consistency: &str) -> Result<SerialConsistency, SmartnessError> {
+    match serial_consistency.trim().to_uppercase().as_str() {
+        "SERIAL" => Ok(SerialConsistency::Serial),
+        "LOCAL_SERIAL" => Ok(SerialConsistency::LocalSerial),
+        _ => Err(SmartnessError::SerialConsistencyInvalid(
+            serial_consistency.to_owned(),
+        )),
+    }
+}
+
+/// settings of a minimal workload with one write operation and the given fields,
+/// defaults are filled by SmartnessSettings::new as for any workload file
+#[cfg(test)]
+pub fn test_settings(test_name: &str, workload: serde_json::Value) -> SmartnessSettings {
+    load_test_workload(test_name, workload).unwrap()
+}
+
+/// like test_settings, but errors of SmartnessSettings::new are returned
+#[cfg(test)]
+pub fn load_test_workload(
+    test_name: &str,
+    workload: serde_json::Value,
+) -> Result<SmartnessSettings, SmartnessError> {
+    let mut minimal_workload = serde_json::json!({
+        "dataset_path": "dataset.csv",
+        "metrics_dir": "metrics",
+        "cycles": 10,
+        "cassandra_host": "127.0.0.1",
+        "cassandra_port": 9042,
+        "operations": [
+            { "name": "w", "script": "INSERT INTO t (id, v) VALUES (?, ?)", "weight": 1 }
+        ]
+    });
+    for (field, value) in workload.as_object().unwrap() {
+        minimal_workload[field] = value.clone();
+    }
+
+    let path = env::temp_dir().join(format!("smartness-workload-{}.json", test_name));
+    std::fs::write(&path, minimal_workload.to_string()).unwrap();
+    SmartnessSettings::new(path.to_string_lossy().into_owned(), false)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    // settings as deserialized from a workload, before defaults are filled
+    fn settings(mut value: Value) -> SmartnessSettings {
+        value["dataset_path"] = json!("dataset.csv");
+        value["metrics_dir"] = json!("metrics");
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn validate_operations_requires_a_key_writer_for_key_operations() {
+        let mut only_reads = settings(json!({
+            "operations": [
+                { "name": "r", "script": "SELECT * FROM t WHERE id = ?", "weight": 1, "binding": "key" },
+                { "name": "w", "script": "INSERT INTO t (id) VALUES (now())", "weight": 1, "binding": "none" }
+            ]
+        }));
+        assert!(matches!(
+            only_reads.validate_operations(),
+            Err(SmartnessError::OperationKeyWriterRequired(name)) if name == "r"
+        ));
+
+        let mut with_writer = settings(json!({
+            "operations": [
+                { "name": "r", "script": "SELECT * FROM t WHERE id = ?", "weight": 1, "binding": "key" },
+                { "name": "w", "script": "INSERT INTO t (id, v) VALUES (?, ?)", "weight": 1 }
+            ]
+        }));
+        assert!(with_writer.validate_operations().is_ok());
+    }
+
+    #[test]
+    fn validate_operations_sets_keys_from_to_the_only_key_writer() {
+        let mut one_writer = settings(json!({
+            "operations": [
+                { "name": "r", "script": "SELECT * FROM t WHERE id = ?", "weight": 1, "binding": "key" },
+                { "name": "w", "script": "INSERT INTO t (id, v) VALUES (?, ?)", "weight": 1 },
+                { "name": "u", "script": "UPDATE t SET v = 1 WHERE id = now()", "weight": 1, "binding": "none" }
+            ]
+        }));
+        one_writer.validate_operations().unwrap();
+        let operations = one_writer.operations.unwrap();
+        assert_eq!(operations[0].keys_from.as_deref(), Some("w"));
+        assert_eq!(operations[1].keys_from, None);
+    }
+
+    #[test]
+    fn validate_operations_requires_keys_from_of_a_key_writer() {
+        let operations = |keys_from: Value| {
+            json!([
+                { "name": "r", "script": "SELECT * FROM t WHERE id = ?", "weight": 1, "binding": "key", "keys_from": keys_from },
+                { "name": "w1", "script": "INSERT INTO t (id, v) VALUES (?, ?)", "weight": 1 },
+                { "name": "w2", "script": "INSERT INTO u (id, v) VALUES (?, ?)", "weight": 1 },
+                { "name": "s", "script": "SELECT * FROM t", "weight": 1, "binding": "none" }
+            ])
+        };
+
+        let mut ambiguous = settings(json!({ "operations": operations(Value::Null) }));
+        assert!(matches!(
+            ambiguous.validate_operations(),
+            Err(SmartnessError::OperationKeysFromRequired(name)) if name == "r"
+        ));
+
+        let mut not_a_writer = settings(json!({ "operations": operations(json!("s")) }));
+        assert!(matches!(
+            not_a_writer.validate_operations(),
+            Err(SmartnessError::OperationKeysFromInvalid(name, keys_from))
+                if name == "r" && keys_from == "s"
+        ));
+
+        let mut named = settings(json!({ "operations": operations(json!("w2")) }));
+        assert!(named.validate_operations().is_ok());
+    }
+
+    #[test]
+    fn validate_operations_requires_schema_keyspace_for_operation_tables() {
+        let operations = json!([
+            { "name": "w", "script": "INSERT INTO t (id, v) VALUES (?, ?)", "weight": 1, "schema_table": "t" }
+        ]);
+
+        let mut without_keyspace = settings(json!({ "operations": operations }));
+        assert!(matches!(
+            without_keyspace.validate_operations(),
+            Err(SmartnessError::OperationSchemaKeyspaceRequired(name)) if name == "w"
+        ));
+
+        let mut with_keyspace =
+            settings(json!({ "operations": operations, "schema_keyspace": "ks" }));
+        assert!(with_keyspace.validate_operations().is_ok());
+    }
+
+    #[test]
+    fn parse_consistency_is_case_insensitive() {
+        assert_eq!(parse_consistency("ONE").unwrap(), Consistency::One);
+        assert_eq!(
+            parse_consistency(" local_quorum ").unwrap(),
+            Consistency::LocalQuorum
+        );
+        assert_eq!(
+            parse_consistency("Local_Serial").unwrap(),
+            Consistency::LocalSerial
+        );
+        assert!(matches!(
+            parse_consistency("FIVE"),
+            Err(SmartnessError::ConsistencyInvalid(name)) if name == "FIVE"
+        ));
+    }
+
+    #[test]
+    fn parse_serial_consistency_accepts_only_serial_levels() {
+        assert_eq!(
+            parse_serial_consistency("serial").unwrap(),
+            SerialConsistency::Serial
+        );
+        assert_eq!(
+            parse_serial_consistency("LOCAL_SERIAL").unwrap(),
+            SerialConsistency::LocalSerial
+        );
+        assert!(matches!(
+            parse_serial_consistency("QUORUM"),
+            Err(SmartnessError::SerialConsistencyInvalid(name)) if name == "QUORUM"
+        ));
+    }
+
+    #[test]
+    fn consistency_levels_are_parsed_when_deserialized() {
+        let levels = settings(json!({
+            "write_consistency": "quorum",
+            "read_consistency": "LOCAL_ONE",
+            "serial_consistency": "LOCAL_SERIAL"
+        }));
+        assert_eq!(
+            levels.write_consistency.as_ref().unwrap().consistency,
+            Consistency::Quorum
+        );
+        assert_eq!(
+            levels.read_consistency.as_ref().unwrap().consistency,
+            Consistency::LocalOne
+        );
+        assert_eq!(
+            levels
+                .serial_consistency
+                .as_ref()
+                .unwrap()
+                .serial_consistency,
+            SerialConsistency::LocalSerial
+        );
+
+        // names are saved as written in the workload
+        let saved = serde_json::to_value(&levels).unwrap();
+        assert_eq!(saved["write_consistency"], json!("quorum"));
+
+        let invalid = serde_json::from_value::<SmartnessSettings>(json!({
+            "dataset_path": "dataset.csv",
+            "metrics_dir": "metrics",
+            "write_consistency": "FIVE"
+        }));
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn metrics_settings_have_defaults() {
+        let smartness_settings = test_settings("metrics-defaults", json!({}));
+        assert_eq!(smartness_settings.metrics_interval_ms, Some(1000));
+        assert_eq!(smartness_settings.metrics_histogram_precision, Some(3));
+        assert_eq!(
+            smartness_settings.metrics_percentiles,
+            Some(vec![75.0, 95.0, 98.0, 99.0, 99.9])
+        );
+        for enabled in [
+            smartness_settings.metrics_overall_enabled,
+            smartness_settings.metrics_drained_enabled,
+            smartness_settings.metrics_windowed_enabled,
+            smartness_settings.metrics_interval_enabled,
+        ] {
+            assert_eq!(enabled, Some(true));
+        }
+    }
+
+    #[test]
+    fn metrics_percentiles_must_be_unique_and_up_to_100() {
+        for (percentile, test_name) in [(0.0, "zero"), (-1.0, "negative"), (100.5, "over")] {
+            assert!(matches!(
+                load_test_workload(
+                    &format!("percentile-{}", test_name),
+                    json!({ "metrics_percentiles": [50, percentile] })
+                ),
+                Err(SmartnessError::MetricsPercentileInvalid(invalid)) if invalid == percentile
+            ));
+        }
+
+        assert!(matches!(
+            load_test_workload(
+                "percentile-duplicated",
+                json!({ "metrics_percentiles": [99, 99.9, 99] })
+            ),
+            Err(SmartnessError::MetricsPercentileDuplicated(99.0))
+        ));
+
+        let smartness_settings = test_settings(
+            "percentile-valid",
+            json!({ "metrics_percentiles": [0.1, 50, 100] }),
+        );
+        assert_eq!(
+            smartness_settings.metrics_percentiles,
+            Some(vec![0.1, 50.0, 100.0])
+        );
+    }
+
+    #[test]
+    fn metrics_interval_and_precision_are_validated() {
+        assert!(matches!(
+            load_test_workload("interval-zero", json!({ "metrics_interval_ms": 0 })),
+            Err(SmartnessError::MetricsIntervalInvalid)
+        ));
+        assert!(matches!(
+            load_test_workload(
+                "precision-over",
+                json!({ "metrics_histogram_precision": 6 })
+            ),
+            Err(SmartnessError::MetricsHistogramPrecisionInvalid)
+        ));
+    }
+
+    #[test]
+    fn contact_points_default_to_cassandra_host_and_port() {
+        let smartness_settings = test_settings("contact-points-default", json!({}));
+        assert_eq!(
+            smartness_settings.cassandra_contact_points,
+            Some(vec!["127.0.0.1:9042".to_string()])
+        );
+
+        let smartness_settings = test_settings(
+            "contact-points-set",
+            json!({ "cassandra_contact_points": ["10.0.0.1:9042", "10.0.0.2:9042"] }),
+        );
+        assert_eq!(
+            smartness_settings.cassandra_contact_points,
+            Some(vec![
+                "10.0.0.1:9042".to_string(),
+                "10.0.0.2:9042".to_string()
+            ])
+        );
+
+        assert!(matches!(
+            load_test_workload(
+                "contact-points-empty",
+                json!({ "cassandra_contact_points": [] })
+            ),
+            Err(SmartnessError::CassandraContactPointsRequired)
+        ));
+    }
+
+    #[test]
+    fn load_balancing_policy_defaults_to_token_aware() {
+        let smartness_settings = test_settings("load-balancing-default", json!({}));
+        assert!(matches!(
+            smartness_settings.load_balancing_policy,
+            Some(LoadBalancingPolicyKind::TokenAware)
+        ));
+        assert_eq!(smartness_settings.permit_dc_failover, Some(false));
+    }
+
+    #[test]
+    fn dc_and_rack_aware_policies_require_local_datacenter() {
+        for (policy, test_name) in [
+            (json!({ "type": "dc_aware_round_robin" }), "dc-aware"),
+            (
+                json!({ "type": "rack_aware", "rack": "rack1" }),
+                "rack-aware",
+            ),
+        ] {
+            assert!(matches!(
+                load_test_workload(
+                    &format!("{}-without-dc", test_name),
+                    json!({ "load_balancing_policy": policy })
+                ),
+                Err(SmartnessError::LocalDatacenterRequired)
+            ));
+
+            let smartness_settings = test_settings(
+                &format!("{}-with-dc", test_name),
+                json!({ "load_balancing_policy": policy, "local_datacenter": "dc1" }),
+            );
+            assert_eq!(smartness_settings.local_datacenter.as_deref(), Some("dc1"));
+        }
+
+        let smartness_settings = test_settings(
+            "rack-aware-rack",
+            json!({
+                "load_balancing_policy": { "type": "rack_aware", "rack": "rack1" },
+                "local_datacenter": "dc1"
+            }),
+        );
+        assert!(matches!(
+            smartness_settings.load_balancing_policy,
+            Some(LoadBalancingPolicyKind::RackAware { rack }) if rack == "rack1"
+        ));
+    }
+
+    #[test]
+    fn latency_aware_threshold_is_at_least_one() {
+        assert!(matches!(
+            load_test_workload(
+                "latency-aware-below-one",
+                json!({
+                    "load_balancing_policy": { "type": "latency_aware", "exclusion_threshold": 0.5 }
+                })
+            ),
+            Err(SmartnessError::LatencyAwareThresholdInvalid)
+        ));
+
+        let smartness_settings = test_settings(
+            "latency-aware-threshold",
+            json!({
+                "load_balancing_policy": { "type": "latency_aware", "exclusion_threshold": 1.5 }
+            }),
+        );
+        assert!(matches!(
+            smartness_settings.load_balancing_policy,
+            Some(LoadBalancingPolicyKind::LatencyAware {
+                exclusion_threshold: Some(1.5)
+            })
+        ));
+
+        // threshold is optional, the driver default is used when it is not set
+        let smartness_settings = test_settings(
+            "latency-aware-default",
+            json!({ "load_balancing_policy": { "type": "latency_aware" } }),
+        );
+        assert!(matches!(
+            smartness_settings.load_balancing_policy,
+            Some(LoadBalancingPolicyKind::LatencyAware {
+                exclusion_threshold: None
+            })
+        ));
+    }
+
+    #[test]
+    fn unknown_load_balancing_policy_is_rejected() {
+        assert!(matches!(
+            load_test_workload(
+                "load-balancing-unknown",
+                json!({ "load_balancing_policy": { "type": "round_robin" } })
+            ),
+            Err(SmartnessError::WorkloadFileDeserializationError(_))
+        ));
+    }
+
+    #[test]
+    fn tls_settings_are_validated() {
+        assert!(matches!(
+            load_test_workload("tls-without-ca", json!({ "tls": {} })),
+            Err(SmartnessError::TlsCaCertRequired)
+        ));
+
+        for (client, test_name) in [
+            (json!({ "client_cert_path": "client.pem" }), "cert"),
+            (json!({ "client_key_path": "client.key" }), "key"),
+        ] {
+            let mut tls = client;
+            tls["ca_cert_path"] = json!("ca.pem");
+            assert!(matches!(
+                load_test_workload(
+                    &format!("tls-only-client-{}", test_name),
+                    json!({ "tls": tls })
+                ),
+                Err(SmartnessError::TlsClientCertAndKeyRequired)
+            ));
+        }
+
+        let smartness_settings = test_settings(
+            "tls-defaults",
+            json!({ "tls": { "ca_cert_path": "ca.pem" } }),
+        );
+        assert_eq!(
+            smartness_settings.tls.unwrap().hostname_verification,
+            Some(true)
+        );
+
+        let smartness_settings = test_settings(
+            "tls-no-hostname-verification",
+            json!({ "tls": { "ca_cert_path": "ca.pem", "hostname_verification": false } }),
+        );
+        assert_eq!(
+            smartness_settings.tls.unwrap().hostname_verification,
+            Some(false)
+        );
+    }
+
+    fn phase(value: Value) -> PhaseSettings {
+        serde_json::from_value(value).unwrap()
+    }
+
+    // two reads and two writes, weights 3, 1, 2 and 0
+    fn mixed_settings() -> SmartnessSettings {
+        settings(json!({
+            "operations": [
+                { "name": "r1", "script": "SELECT * FROM t", "weight": 3, "binding": "none" },
+                { "name": "r2", "script": "SELECT * FROM u", "weight": 1, "binding": "none" },
+                { "name": "w1", "script": "INSERT INTO t (id) VALUES (?)", "weight": 2 },
+                { "name": "w2", "script": "INSERT INTO u (id) VALUES (?)", "weight": 0 }
+            ]
+        }))
+    }
+
+    #[test]
+    fn phase_weights_default_to_operation_weights() {
+        let settings = mixed_settings();
+
+        let weights = settings.phase_weights(&phase(json!({ "name": "p" })));
+        assert_eq!(weights, vec![3, 1, 2, 0]);
+    }
+
+    #[test]
+    fn phase_weights_override_weights_by_operation_name() {
+        let settings = mixed_settings();
+
+        let weights = settings.phase_weights(&phase(
+            json!({ "name": "p", "operations": { "r2": 5, "w2": 4 } }),
+        ));
+        assert_eq!(weights, vec![3, 5, 2, 4]);
+    }
+
+    #[test]
+    fn phase_weights_scale_kinds_by_reads_rate() {
+        let settings = mixed_settings();
+
+        // reads keep their 3:1 ratio in 80% of tasks, writes their 2:0 ratio in 20%...
+        let weights = settings.phase_weights(&phase(json!({ "name": "p", "reads_rate": 0.8 })));
+        assert_eq!(weights, vec![600, 200, 200, 0]);
+        assert_eq!(weights.iter().sum::<u32>(), PHASE_WEIGHT_SCALE as u32);
+    }
+
+    #[test]
+    fn phase_weights_split_share_equally_when_kind_has_no_weight() {
+        let settings = mixed_settings();
+
+        let weights = settings.phase_weights(&phase(json!({
+            "name": "p",
+            "reads_rate": 0.5,
+            "operations": { "w1": 0 }
+        })));
+        assert_eq!(weights, vec![375, 125, 250, 250]);
+    }
+
+    // credentials file in temp dir, named by test so tests do not share it
+    fn credentials_file(test_name: &str, credentials: Value) -> String {
+        let path = env::temp_dir().join(format!("smartness-credentials-{}.json", test_name));
+        std::fs::write(&path, credentials.to_string()).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn env_vars(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn resolve_credentials_prefers_env_vars_then_file_then_workload() {
+        let credentials_path = credentials_file(
+            "precedence",
+            json!({ "username": "file-user", "password": "file-password" }),
+        );
+        let workload = json!({
+            "cassandra_username": "workload-user",
+            "cassandra_password": "workload-password",
+            "cassandra_credentials_path": credentials_path
+        });
+
+        let mut from_env = settings(workload.clone());
+        from_env
+            .resolve_credentials(env_vars(&[
+                (CASSANDRA_USERNAME_ENV, "env-user"),
+                (CASSANDRA_PASSWORD_ENV, "env-password"),
+            ]))
+            .unwrap();
+        assert_eq!(from_env.cassandra_username.as_deref(), Some("env-user"));
+        assert_eq!(from_env.cassandra_password.as_deref(), Some("env-password"));
+
+        // each credential falls back on its own...
+        let mut mixed = settings(workload.clone());
+        mixed
+            .resolve_credentials(env_vars(&[(CASSANDRA_PASSWORD_ENV, "env-password")]))
+            .unwrap();
+        assert_eq!(mixed.cassandra_username.as_deref(), Some("file-user"));
+        assert_eq!(mixed.cassandra_password.as_deref(), Some("env-password"));
+
+        let mut from_workload = settings(json!({
+            "cassandra_username": "workload-user",
+            "cassandra_password": "workload-password"
+        }));
+        from_workload.resolve_credentials(env_vars(&[])).unwrap();
+        assert_eq!(
+            from_workload.cassandra_username.as_deref(),
+            Some("workload-user")
+        );
+        assert_eq!(
+            from_workload.cassandra_password.as_deref(),
+            Some("workload-password")
+        );
+    }
+
+    #[test]
+    fn resolve_credentials_ignores_empty_env_vars() {
+        let credentials_path = credentials_file(
+            "empty-env",
+            json!({ "username": "file-user", "password": "file-password" }),
+        );
+        let mut settings = settings(json!({ "cassandra_credentials_path": credentials_path }));
+
+        settings
+            .resolve_credentials(env_vars(&[
+                (CASSANDRA_USERNAME_ENV, ""),
+                (CASSANDRA_PASSWORD_ENV, ""),
+            ]))
+            .unwrap();
+        assert_eq!(settings.cassandra_username.as_deref(), Some("file-user"));
+        assert_eq!(
+            settings.cassandra_password.as_deref(),
+            Some("file-password")
+        );
+    }
+
+    #[test]
+    fn resolve_credentials_sets_authenticator_only_with_credentials() {
+        let mut without_auth = settings(json!({}));
+        without_auth.resolve_credentials(env_vars(&[])).unwrap();
+        assert!(without_auth.cassandra_username.is_none());
+        assert!(without_auth.cassandra_authenticator.is_none());
+
+        let mut with_auth = settings(json!({}));
+        with_auth
+            .resolve_credentials(env_vars(&[
+                (CASSANDRA_USERNAME_ENV, "user"),
+                (CASSANDRA_PASSWORD_ENV, "password"),
+            ]))
+            .unwrap();
+        assert!(matches!(
+            with_auth.cassandra_authenticator,
+            Some(AuthenticatorKind::Password)
+        ));
+
+        let mut authenticator_only = settings(json!({
+            "cassandra_authenticator": { "type": "sasl_plain" }
+        }));
+        assert!(matches!(
+            authenticator_only.resolve_credentials(env_vars(&[])),
+            Err(SmartnessError::CassandraAuthenticatorCredentialsRequired)
+        ));
+    }
+
+    #[test]
+    fn resolve_credentials_requires_username_and_password_together() {
+        let mut settings = settings(json!({ "cassandra_username": "user" }));
+        assert!(matches!(
+            settings.resolve_credentials(env_vars(&[])),
+            Err(SmartnessError::CassandraUsernameAndPasswordAreRequired)
+        ));
+    }
+
+    #[test]
+    fn missing_credentials_file_means_no_credentials() {
+        let mut without_auth = settings(json!({
+            "cassandra_credentials_path": "/nonexistent/smartness-credentials.json"
+        }));
+        without_auth.resolve_credentials(env_vars(&[])).unwrap();
+        assert!(without_auth.cassandra_username.is_none());
+        assert!(without_auth.cassandra_authenticator.is_none());
+
+        // workload credentials are still used
+        let mut from_workload = settings(json!({
+            "cassandra_username": "workload-user",
+            "cassandra_password": "workload-password",
+            "cassandra_credentials_path": "/nonexistent/smartness-credentials.json"
+        }));
+        from_workload.resolve_credentials(env_vars(&[])).unwrap();
+        assert_eq!(
+            from_workload.cassandra_username.as_deref(),
+            Some("workload-user")
+        );
+    }
+
+    #[test]
+    fn credentials_file_is_not_read_when_env_vars_set_both_credentials() {
+        // an invalid file fails only when it is read
+        let credentials_path = credentials_file("invalid", json!("not credentials"));
+        let workload = json!({ "cassandra_credentials_path": credentials_path });
+
+        let mut from_env = settings(workload.clone());
+        from_env
+            .resolve_credentials(env_vars(&[
+                (CASSANDRA_USERNAME_ENV, "env-user"),
+                (CASSANDRA_PASSWORD_ENV, "env-password"),
+            ]))
+            .unwrap();
+        assert_eq!(from_env.cassandra_username.as_deref(), Some("env-user"));
+
+        let mut only_username = settings(workload);
+        assert!(matches!(
+            only_username.resolve_credentials(env_vars(&[(CASSANDRA_USERNAME_ENV, "env-user")])),
+            Err(SmartnessError::CredentialsFileDeserializationError(path, _))
+                if path == credentials_path
+        ));
+    }
+
+    #[test]
+    fn credentials_path_is_relative_to_the_workload_file() {
+        let workload_dir = env::temp_dir().join("smartness-workload-dir");
+        std::fs::create_dir_all(&workload_dir).unwrap();
+        std::fs::write(
+            workload_dir.join("smartness-credentials.json"),
+            json!({ "username": "file-user", "password": "file-password" }).to_string(),
+        )
+        .unwrap();
+
+        let mut relative = settings(json!({
+            "cassandra_credentials_path": "smartness-credentials.json"
+        }));
+        relative.resolve_credentials_path(&workload_dir.join("smartness-workload.json"));
+        assert_eq!(
+            relative.cassandra_credentials_path.as_deref(),
+            Some(
+                workload_dir
+                    .join("smartness-credentials.json")
+                    .to_string_lossy()
+                    .as_ref()
+            )
+        );
+
+        relative.resolve_credentials(env_vars(&[])).unwrap();
+        assert_eq!(relative.cassandra_username.as_deref(), Some("file-user"));
+
+        // absolute paths and workloads in the current directory keep the path
+        let mut absolute = settings(json!({
+            "cassandra_credentials_path": "/etc/smartness-credentials.json"
+        }));
+        absolute.resolve_credentials_path(&workload_dir.join("smartness-workload.json"));
+        assert_eq!(
+            absolute.cassandra_credentials_path.as_deref(),
+            Some("/etc/smartness-credentials.json")
+        );
+
+        let mut current_dir = settings(json!({
+            "cassandra_credentials_path": "smartness-credentials.json"
+        }));
+        current_dir.resolve_credentials_path(Path::new("smartness-workload.json"));
+        assert_eq!(
+            current_dir.cassandra_credentials_path.as_deref(),
+            Some("smartness-credentials.json")
+        );
+    }
 }
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use scylla::authentication::{
+    AuthError, AuthenticatorProvider, AuthenticatorSession, PlainTextAuthenticator,
+};
+
+use crate::config::smarteness_settings::{AuthenticatorKind, SmartnessSettings};
+
+/// create the authenticator of workload, None if auth is disabled...
+/// credentials are validated by SmartnessSettings, so they are set with cassandra_authenticator.
+pub fn create_authenticator(
+    smartness_settings: &SmartnessSettings,
+) -> Option<Arc<dyn AuthenticatorProvider>> {
+    let authenticator = smartness_settings.cassandra_authenticator.as_ref()?;
+    let username = smartness_settings.cassandra_username.clone().unwrap();
+    let password = smartness_settings.cassandra_password.clone().unwrap();
+
+    Some(match authenticator {
+        AuthenticatorKind::Password => Arc::new(PlainTextAuthenticator::new(username, password)),
+        AuthenticatorKind::SaslPlain { authorization_id } => Arc::new(SaslPlainAuthenticator {
+            authorization_id: authorization_id.clone().unwrap_or_default(),
+            username,
+            password,
+        }),
+    })
+}
+
+/// SASL PLAIN authenticator, sends {authorization_id}\0{username}\0{password}...
+/// an empty authorization_id makes nodes act as the username.
+struct SaslPlainAuthenticator {
+    authorization_id: String,
+    username: String,
+    password: String,
+}
+
+#[async_trait]
+impl AuthenticatorProvider for SaslPlainAuthenticator {
+    async fn start_authentication_session(
+        &self,
+        _authenticator_name: &str,
+    ) -> Result<(Option<Vec<u8>>, Box<dyn AuthenticatorSession>), AuthError> {
+        let response = [
+            self.authorization_id.as_bytes(),
+            self.username.as_bytes(),
+            self.password.as_bytes(),
+        ]
+        .join(&0);
+
+        Ok((Some(response), Box::new(SaslPlainSession)))
+    }
+}
+
+// the whole PLAIN exchange is the initial response, nodes only send success after it
+struct SaslPlainSession;
+
+#[async_trait]
+impl AuthenticatorSession for SaslPlainSession {
+    async fn evaluate_challenge(
+        &mut self,
+        _token: Option<&[u8]>,
+    ) -> Result<Option<Vec<u8>>, AuthError> {
+        Err("challenges are not expected by SASL PLAIN authentication".to_owned())
+    }
+
+    async fn success(&mut self, _token: Option<&[u8]>) -> Result<(), AuthError> {
+        Ok(())
+    }
+}
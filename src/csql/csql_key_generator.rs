@@ -0,0 +1,162 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use rand::Rng;
+use scylla::value::{CqlTimeuuid, CqlValue};
+use uuid::Uuid;
+
+use crate::config::smarteness_settings::{KeyGenerator, SmartnessSettings};
+
+const ORDER_TYPE: Ordering = Ordering::Relaxed;
+
+/// generates keys bound before dataset columns, shared by warmup and all tasks
+/// so sequences are not repeated.
+pub struct CsqlKeyGenerator {
+    key_generator: KeyGenerator,
+    sequence: AtomicI64,
+    node_id: [u8; 6],
+}
+
+impl CsqlKeyGenerator {
+    pub fn new(smartness_settings: &SmartnessSettings) -> Self {
+        let key_generator = smartness_settings.key_generator.unwrap();
+        let start = match key_generator {
+            KeyGenerator::Sequential { start } | KeyGenerator::HashedSequence { start } => {
+                start.unwrap_or(0)
+            }
+            _ => 0,
+        };
+
+        CsqlKeyGenerator {
+            key_generator,
+            sequence: AtomicI64::new(start),
+            // timeuuid node id is random per process...
+            node_id: rand::rng().random(),
+        }
+    }
+
+    /// quantity of bound values used by a key
+    pub fn key_len(&self) -> usize {
+        match self.key_generator {
+            KeyGenerator::Composite { .. } => 2,
+            _ => 1,
+        }
+    }
+
+    /// push the next key into values
+    pub fn generate(&self, values: &mut Vec<CqlValue>) {
+        match self.key_generator {
+            KeyGenerator::UuidV4 => values.push(CqlValue::Uuid(Uuid::new_v4())),
+            KeyGenerator::UuidV7 => values.push(CqlValue::Uuid(Uuid::now_v7())),
+            KeyGenerator::TimeuuidV1 => values.push(CqlValue::Timeuuid(CqlTimeuuid::from(
+                Uuid::now_v1(&self.node_id),
+            ))),
+            KeyGenerator::Sequential { .. } => {
+                values.push(CqlValue::BigInt(self.sequence.fetch_add(1, ORDER_TYPE)))
+            }
+            KeyGenerator::HashedSequence { .. } => values.push(CqlValue::BigInt(hash_sequence(
+                self.sequence.fetch_add(1, ORDER_TYPE),
+            ))),
+            KeyGenerator::Composite { partitions } => {
+                let partition = rand::rng().random_range(0..partitions);
+                values.push(CqlValue::BigInt(partition as i64));
+                values.push(CqlValue::BigInt(self.sequence.fetch_add(1, ORDER_TYPE)));
+            }
+        }
+    }
+}
+
+// splitmix64 finalizer, it is a bijection, so hashed keys are unique as the sequence...
+fn hash_sequence(sequence: i64) -> i64 {
+    let mut z = sequence as u64;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (z ^ (z >> 31)) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn key_generator(key_generator: KeyGenerator, start: i64) -> CsqlKeyGenerator {
+        CsqlKeyGenerator {
+            key_generator,
+            sequence: AtomicI64::new(start),
+            node_id: [1, 2, 3, 4, 5, 6],
+        }
+    }
+
+    fn generate(key_generator: &CsqlKeyGenerator) -> Vec<CqlValue> {
+        let mut values = Vec::new();
+        key_generator.generate(&mut values);
+        assert_eq!(values.len(), key_generator.key_len());
+        values
+    }
+
+    #[test]
+    fn sequential_keys_increment_from_start() {
+        let key_generator = key_generator(KeyGenerator::Sequential { start: Some(10) }, 10);
+        let keys: Vec<_> = (0..3).map(|_| generate(&key_generator)).collect();
+        assert_eq!(
+            keys,
+            [10, 11, 12].map(|sequence| vec![CqlValue::BigInt(sequence)])
+        );
+    }
+
+    #[test]
+    fn hashed_sequence_keys_are_unique_and_not_ordered() {
+        let key_generator = key_generator(KeyGenerator::HashedSequence { start: Some(5) }, 5);
+        let keys: Vec<i64> = (0..10_000)
+            .map(|_| match generate(&key_generator).as_slice() {
+                [CqlValue::BigInt(key)] => *key,
+                key => panic!("unexpected key {:?}", key),
+            })
+            .collect();
+
+        assert_eq!(keys[0], hash_sequence(5));
+        assert_eq!(keys.iter().collect::<HashSet<_>>().len(), keys.len());
+        assert!(keys.windows(2).any(|pair| pair[0] > pair[1]));
+        assert!(keys.windows(2).any(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn hash_sequence_spreads_consecutive_sequences() {
+        assert_ne!(hash_sequence(1), 1);
+        assert_ne!(hash_sequence(1), hash_sequence(2));
+        // negative hashes are valid bigint keys, so the whole range is used
+        assert!((0..100).map(hash_sequence).any(|hash| hash < 0));
+    }
+
+    #[test]
+    fn composite_keys_have_a_partition_and_an_incremented_clustering_key() {
+        let key_generator = key_generator(KeyGenerator::Composite { partitions: 4 }, 0);
+        assert_eq!(key_generator.key_len(), 2);
+
+        for expected_clustering in 0..100 {
+            match generate(&key_generator).as_slice() {
+                [CqlValue::BigInt(partition), CqlValue::BigInt(clustering)] => {
+                    assert!((0..4).contains(partition));
+                    assert_eq!(*clustering, expected_clustering);
+                }
+                key => panic!("unexpected key {:?}", key),
+            }
+        }
+    }
+
+    #[test]
+    fn uuid_keys_have_the_version_of_their_generator() {
+        for (generator, version) in [
+            (KeyGenerator::UuidV4, 4),
+            (KeyGenerator::UuidV7, 7),
+            (KeyGenerator::TimeuuidV1, 1),
+        ] {
+            let uuid = match generate(&key_generator(generator, 0)).as_slice() {
+                [CqlValue::Uuid(uuid)] => *uuid,
+                [CqlValue::Timeuuid(timeuuid)] => Uuid::from(*timeuuid),
+                key => panic!("unexpected key {:?}", key),
+            };
+            assert_eq!(uuid.get_version_num(), version);
+        }
+    }
+}
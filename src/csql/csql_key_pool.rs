@@ -0,0 +1,228 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use rand::Rng;
+use rand_distr::{Distribution, Zipf};
+use scylla::value::CqlValue;
+
+use crate::config::smarteness_settings::{KeyDistribution, SmartnessSettings};
+
+/// bounded pool of keys written by a write operation bound to dataset,
+/// operations bound to key read them back, so reads target existing rows.
+pub struct CsqlKeyPool {
+    keys: Mutex<PooledKeys>,
+    capacity: usize,
+    distribution: KeyDistribution,
+    zipfian_exponent: f64,
+}
+
+/// keys ordered from the oldest to the latest and the zipf distribution used to pick them
+struct PooledKeys {
+    keys: VecDeque<Vec<CqlValue>>,
+    /// zipf over the quantity of keys it was created for, created again only when it changes
+    zipf: Option<(usize, Zipf<f64>)>,
+}
+
+impl PooledKeys {
+    fn with_capacity(capacity: usize) -> Self {
+        PooledKeys {
+            keys: VecDeque::with_capacity(capacity),
+            zipf: None,
+        }
+    }
+
+    /// zipfian rank from 0 to len - 1, lower ranks are more likely
+    fn zipfian_rank<R: Rng>(&mut self, zipfian_exponent: f64, rng: &mut R) -> usize {
+        let len = self.keys.len();
+        let zipf = match &self.zipf {
+            Some((zipf_len, zipf)) if *zipf_len == len => zipf,
+            _ => {
+                // len and exponent are greater than 0, so zipf is always valid...
+                let zipf = Zipf::new(len as f64, zipfian_exponent).unwrap();
+                &self.zipf.insert((len, zipf)).1
+            }
+        };
+        (zipf.sample(rng) as usize - 1).min(len - 1)
+    }
+}
+
+impl CsqlKeyPool {
+    pub fn new(smartness_settings: &SmartnessSettings) -> Self {
+        let capacity = smartness_settings.key_pool_size.unwrap();
+
+        CsqlKeyPool {
+            keys: Mutex::new(PooledKeys::with_capacity(capacity)),
+            capacity,
+            distribution: smartness_settings.key_distribution.unwrap(),
+            zipfian_exponent: smartness_settings.key_zipfian_exponent.unwrap(),
+        }
+    }
+
+    /// keep values of a written key, the oldest one is dropped when the pool is full
+    pub fn add(&self, key: Vec<CqlValue>) {
+        let keys = &mut self.keys.lock().unwrap().keys;
+        if keys.len() == self.capacity {
+            keys.pop_front();
+        }
+        keys.push_back(key);
+    }
+
+    /// true until the first key is written
+    pub fn is_empty(&self) -> bool {
+        self.keys.lock().unwrap().keys.is_empty()
+    }
+
+    /// pick a key using the configured distribution, None if no key was written yet
+    pub fn pick(&self) -> Option<Vec<CqlValue>> {
+        let mut pooled_keys = self.keys.lock().unwrap();
+        let len = pooled_keys.keys.len();
+        if len == 0 {
+            return None;
+        }
+
+        let mut rng = rand::rng();
+        // keys are ordered from the oldest to the latest...
+        let index = match self.distribution {
+            KeyDistribution::Uniform => rng.random_range(0..len),
+            KeyDistribution::Latest => {
+                len - 1 - pooled_keys.zipfian_rank(self.zipfian_exponent, &mut rng)
+            }
+            KeyDistribution::Zipfian => pooled_keys.zipfian_rank(self.zipfian_exponent, &mut rng),
+        };
+
+        pooled_keys.keys.get(index).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_pool(capacity: usize, distribution: KeyDistribution) -> CsqlKeyPool {
+        CsqlKeyPool {
+            keys: Mutex::new(PooledKeys::with_capacity(capacity)),
+            capacity,
+            distribution,
+            zipfian_exponent: 1.5,
+        }
+    }
+
+    fn key(id: i32) -> Vec<CqlValue> {
+        vec![CqlValue::Int(id)]
+    }
+
+    // counts of picked keys by id, ids are 0..len
+    fn pick_counts(key_pool: &CsqlKeyPool, len: usize, picks: usize) -> Vec<usize> {
+        let mut counts = vec![0; len];
+        for _ in 0..picks {
+            match key_pool.pick().unwrap().as_slice() {
+                [CqlValue::Int(id)] => counts[*id as usize] += 1,
+                key => panic!("unexpected key {:?}", key),
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn pick_is_none_until_a_key_is_written() {
+        let key_pool = key_pool(10, KeyDistribution::Uniform);
+        assert!(key_pool.is_empty());
+        assert_eq!(key_pool.pick(), None);
+
+        key_pool.add(key(0));
+        assert!(!key_pool.is_empty());
+        assert_eq!(key_pool.pick(), Some(key(0)));
+    }
+
+    #[test]
+    fn add_drops_the_oldest_key_when_full() {
+        let key_pool = key_pool(3, KeyDistribution::Uniform);
+        for id in 0..5 {
+            key_pool.add(key(id));
+        }
+
+        let keys: Vec<_> = key_pool.keys.lock().unwrap().keys.iter().cloned().collect();
+        assert_eq!(keys, vec![key(2), key(3), key(4)]);
+    }
+
+    #[test]
+    fn pick_stays_within_written_keys_for_all_distributions() {
+        for distribution in [
+            KeyDistribution::Uniform,
+            KeyDistribution::Zipfian,
+            KeyDistribution::Latest,
+        ] {
+            let key_pool = key_pool(100, distribution);
+            for id in 0..20 {
+                key_pool.add(key(id));
+            }
+
+            let counts = pick_counts(&key_pool, 20, 5_000);
+            assert_eq!(counts.iter().sum::<usize>(), 5_000);
+        }
+    }
+
+    #[test]
+    fn zipfian_prefers_oldest_keys_and_latest_prefers_newest_ones() {
+        let zipfian = key_pool(100, KeyDistribution::Zipfian);
+        let latest = key_pool(100, KeyDistribution::Latest);
+        for id in 0..50 {
+            zipfian.add(key(id));
+            latest.add(key(id));
+        }
+
+        let zipfian_counts = pick_counts(&zipfian, 50, 10_000);
+        assert!(zipfian_counts[0] > zipfian_counts[49] * 10);
+
+        let latest_counts = pick_counts(&latest, 50, 10_000);
+        assert!(latest_counts[49] > latest_counts[0] * 10);
+    }
+
+    #[test]
+    fn zipfian_rank_of_single_key_is_zero() {
+        let mut pooled_keys = PooledKeys::with_capacity(1);
+        pooled_keys.keys.push_back(key(0));
+        let mut rng = rand::rng();
+        for _ in 0..100 {
+            assert_eq!(pooled_keys.zipfian_rank(1.5, &mut rng), 0);
+        }
+    }
+
+    #[test]
+    fn zipf_is_created_again_only_when_keys_change() {
+        let key_pool = key_pool(3, KeyDistribution::Zipfian);
+        let zipf_len = || {
+            key_pool
+                .keys
+                .lock()
+                .unwrap()
+                .zipf
+                .as_ref()
+                .map(|zipf| zipf.0)
+        };
+
+        key_pool.add(key(0));
+        key_pool.add(key(1));
+        key_pool.pick();
+        assert_eq!(zipf_len(), Some(2));
+
+        key_pool.add(key(2));
+        key_pool.pick();
+        assert_eq!(zipf_len(), Some(3));
+
+        // the pool is full, so its length and zipf do not change anymore
+        key_pool.add(key(3));
+        key_pool.pick();
+        assert_eq!(zipf_len(), Some(3));
+        assert_eq!(pick_counts_from(&key_pool, 1, 4, 1_000), 1_000);
+    }
+
+    // quantity of picked keys with ids from..to
+    fn pick_counts_from(key_pool: &CsqlKeyPool, from: i32, to: i32, picks: usize) -> usize {
+        (0..picks)
+            .filter(|_| match key_pool.pick().unwrap().as_slice() {
+                [CqlValue::Int(id)] => (from..to).contains(id),
+                _ => false,
+            })
+            .count()
+    }
+}
@@ -1,70 +1,127 @@
-use std::{fs::File, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    fs::File,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use csv::{Reader, StringRecord};
+use csv::{ReaderBuilder, StringRecord};
+use rustls::ClientConfig;
 use scylla::{
-    client::{session::Session, session_builder::SessionBuilder},
+    client::{
+        execution_profile::ExecutionProfile, session::Session, session_builder::SessionBuilder,
+    },
+    errors::{ExecutionError, PrepareError},
+    policies::load_balancing::{DefaultPolicy, LatencyAwarenessBuilder, LoadBalancingPolicy},
     response::PagingState,
+    statement::{Consistency, SerialConsistency, Statement, prepared::PreparedStatement},
     value::CqlValue,
 };
 use tokio::time::sleep;
-use uuid::Uuid;
 
 use crate::{
-    config::smarteness_settings::SmartnessSettings, error::SmartnessError,
-    metrics::metrics_manager::MetricsManager,
+    config::smarteness_settings::{
+        BindingSource, LoadBalancingPolicyKind, OperationKind, SmartnessSettings,
+    },
+    csql::{
+        csql_auth::create_authenticator, csql_operation::CsqlOperation,
+        csql_tls::create_tls_context, csql_types::CsqlRowBinder,
+    },
+    error::SmartnessError,
+    metrics::metrics_error::MetricsErrorKind,
 };
 
+// statement used by write and read tasks...
+// it could be prepared once per session or sent as a raw CQL string in every request.
+#[derive(Clone)]
+pub enum CsqlStatement {
+    Prepared(PreparedStatement),
+    Unprepared(Statement),
+}
+
+impl CsqlStatement {
+    /// create a raw statement, consistency levels are kept when it is prepared...
+    pub fn new(
+        script: &str,
+        consistency: Option<Consistency>,
+        serial_consistency: Option<SerialConsistency>,
+    ) -> Self {
+        let mut statement = Statement::new(script);
+        if let Some(consistency) = consistency {
+            statement.set_consistency(consistency);
+        }
+        if serial_consistency.is_some() {
+            statement.set_serial_consistency(serial_consistency);
+        }
+        CsqlStatement::Unprepared(statement)
+    }
+
+    pub async fn prepare(&self, session: &Session) -> Result<Self, PrepareError> {
+        match self {
+            CsqlStatement::Prepared(prepared) => Ok(CsqlStatement::Prepared(prepared.clone())),
+            CsqlStatement::Unprepared(statement) => session
+                .prepare(statement.clone())
+                .await
+                .map(CsqlStatement::Prepared),
+        }
+    }
+
+    pub async fn execute_unpaged(
+        &self,
+        session: &Session,
+        values: Vec<CqlValue>,
+    ) -> Result<(), ExecutionError> {
+        match self {
+            CsqlStatement::Prepared(prepared) => {
+                session.execute_unpaged(prepared, values).await.map(|_| ())
+            }
+            CsqlStatement::Unprepared(statement) => session
+                .query_unpaged(statement.clone(), values)
+                .await
+                .map(|_| ()),
+        }
+    }
+
+    pub async fn execute_single_page(
+        &self,
+        session: &Session,
+        values: Vec<CqlValue>,
+    ) -> Result<(), ExecutionError> {
+        match self {
+            CsqlStatement::Prepared(prepared) => session
+                .execute_single_page(prepared, values, PagingState::start())
+                .await
+                .map(|_| ()),
+            CsqlStatement::Unprepared(statement) => session
+                .query_single_page(statement.clone(), values, PagingState::start())
+                .await
+                .map(|_| ()),
+        }
+    }
+}
+
 // function that will create a ScyllaDB sessions for writers and readers...
 // this session will be used in other functions...
 pub async fn create_session(
     smartness_settings: &SmartnessSettings,
 ) -> Result<(Session, Session), SmartnessError> {
+    // certificates are loaded once and shared by both sessions...
+    let tls_context = smartness_settings
+        .tls
+        .as_ref()
+        .map(create_tls_context)
+        .transpose()?;
+
     println!("Create Write Session started.");
 
-    let write_session = SessionBuilder::new()
-        .known_node(format!(
-            "{}:{}",
-            smartness_settings.cassandra_host.as_ref().unwrap().clone(),
-            smartness_settings.cassandra_port.unwrap()
-        ))
-        .user(
-            smartness_settings
-                .cassandra_username
-                .as_ref()
-                .unwrap()
-                .clone(),
-            smartness_settings
-                .cassandra_password
-                .as_ref()
-                .unwrap()
-                .clone(),
-        )
-        .connection_timeout(Duration::from_secs(60))
+    let write_session = session_builder(smartness_settings, tls_context.clone())
         .build()
         .await
         .map_err(SmartnessError::ScyllaSessionError)?;
 
     println!("Create Read Session started.");
 
-    let read_session = SessionBuilder::new()
-        .known_node(format!(
-            "{}:{}",
-            smartness_settings.cassandra_host.as_ref().unwrap().clone(),
-            smartness_settings.cassandra_port.unwrap()
-        ))
-        .user(
-            smartness_settings
-                .cassandra_username
-                .as_ref()
-                .unwrap()
-                .clone(),
-            smartness_settings
-                .cassandra_password
-                .as_ref()
-                .unwrap()
-                .clone(),
-        )
-        .connection_timeout(Duration::from_secs(60))
+    let read_session = session_builder(smartness_settings, tls_context)
         .build()
         .await
         .map_err(SmartnessError::ScyllaSessionError)?;
@@ -74,6 +131,67 @@ pub async fn create_session(
     Ok((write_session, read_session))
 }
 
+// builder shared by write and read sessions, with contact points, the authenticator if
+// credentials are set, the load balancing policy of workload and TLS if it is enabled...
+fn session_builder(
+    smartness_settings: &SmartnessSettings,
+    tls_context: Option<Arc<ClientConfig>>,
+) -> SessionBuilder {
+    let execution_profile = ExecutionProfile::builder()
+        .load_balancing_policy(load_balancing_policy(smartness_settings))
+        .build();
+
+    let mut builder = SessionBuilder::new().known_nodes(
+        smartness_settings
+            .cassandra_contact_points
+            .as_ref()
+            .unwrap(),
+    );
+
+    // nodes with auth disabled are connected without authenticator
+    if let Some(authenticator) = create_authenticator(smartness_settings) {
+        builder = builder.authenticator_provider(authenticator);
+    }
+
+    builder
+        .default_execution_profile_handle(execution_profile.into_handle())
+        .tls_context(tls_context)
+        .connection_timeout(Duration::from_secs(60))
+}
+
+// all policies are built from the driver default policy...
+// local_datacenter makes its nodes local, the other ones are only used with permit_dc_failover.
+fn load_balancing_policy(smartness_settings: &SmartnessSettings) -> Arc<dyn LoadBalancingPolicy> {
+    let mut builder =
+        DefaultPolicy::builder().permit_dc_failover(smartness_settings.permit_dc_failover.unwrap());
+
+    let local_datacenter = smartness_settings.local_datacenter.clone();
+    if let Some(local_datacenter) = local_datacenter.clone() {
+        builder = builder.prefer_datacenter(local_datacenter);
+    }
+
+    // local_datacenter is validated by SmartnessSettings for dc and rack aware policies...
+    match smartness_settings.load_balancing_policy.as_ref().unwrap() {
+        LoadBalancingPolicyKind::TokenAware => builder.token_aware(true),
+        LoadBalancingPolicyKind::DcAwareRoundRobin => builder.token_aware(false),
+        LoadBalancingPolicyKind::RackAware { rack } => builder
+            .prefer_datacenter_and_rack(local_datacenter.unwrap(), rack.clone())
+            .token_aware(true),
+        LoadBalancingPolicyKind::LatencyAware {
+            exclusion_threshold,
+        } => {
+            let mut latency_awareness = LatencyAwarenessBuilder::new();
+            if let Some(exclusion_threshold) = exclusion_threshold {
+                latency_awareness = latency_awareness.exclusion_threshold(*exclusion_threshold);
+            }
+            builder
+                .token_aware(true)
+                .latency_awareness(latency_awareness)
+        }
+    }
+    .build()
+}
+
 // function that will apply script as startup step.
 // if startup_enabled setting is true, it will run...
 pub async fn startup_op(
@@ -95,7 +213,7 @@ pub async fn startup_op(
             )
             .await
             .map(|_| ())
-            .map_err(SmartnessError::CsqlCreateKeyspaceError)?;
+            .map_err(|err| SmartnessError::CsqlCreateKeyspaceError(Box::new(err)))?;
 
         println!("Create Schema applied.");
 
@@ -111,7 +229,7 @@ pub async fn startup_op(
             )
             .await
             .map(|_| ())
-            .map_err(SmartnessError::CsqlDropTableError)?;
+            .map_err(|err| SmartnessError::CsqlDropTableError(Box::new(err)))?;
 
         println!("Drop table applied.");
 
@@ -127,7 +245,7 @@ pub async fn startup_op(
             )
             .await
             .map(|_| ())
-            .map_err(SmartnessError::CsqlCreateTableError)?;
+            .map_err(|err| SmartnessError::CsqlCreateTableError(Box::new(err)))?;
 
         println!("Create table applied.");
         println!("Startup Operations finished.");
@@ -139,55 +257,183 @@ pub async fn startup_op(
     Ok(())
 }
 
+// function that will prepare operations scripts once per session...
+// if prepared_statements_enabled setting is false, raw statements will be kept.
+pub async fn prepare_operations(
+    smartness_settings: &SmartnessSettings,
+    operations: &[Arc<CsqlOperation>],
+) -> Result<Vec<Arc<CsqlOperation>>, SmartnessError> {
+    if !smartness_settings.prepared_statements_enabled.unwrap() {
+        println!("Prepared statements disabled, raw statements will be used.");
+        return Ok(operations.to_vec());
+    }
+
+    let mut prepared_operations = Vec::with_capacity(operations.len());
+    for operation in operations {
+        let statement = operation
+            .statement
+            .prepare(&operation.session)
+            .await
+            .map_err(|err| {
+                SmartnessError::CsqlPrepareOperationError(operation.name.clone(), Box::new(err))
+            })?;
+
+        println!("Operation {} prepared.", operation.name);
+
+        prepared_operations.push(Arc::new(CsqlOperation {
+            statement,
+            ..CsqlOperation::clone(operation)
+        }));
+    }
+
+    Ok(prepared_operations)
+}
+
+// function that will load column types used to bind dataset values of each operation...
+// types are read from system_schema.columns if schema_keyspace and a schema_table are set
+// and then overridden by column_types settings. None means all columns are text.
+pub async fn load_column_types(
+    smartness_settings: &SmartnessSettings,
+    session: Arc<Session>,
+    operations: &[Arc<CsqlOperation>],
+) -> Result<Vec<Arc<CsqlOperation>>, SmartnessError> {
+    let operations_settings = smartness_settings.operations.as_ref().unwrap();
+
+    // operations on the same table share its types, so each table is loaded once...
+    let mut schema_column_types = HashMap::new();
+    let mut typed_operations = Vec::with_capacity(operations.len());
+    for (operation, operation_settings) in operations.iter().zip(operations_settings) {
+        if operation.binding != BindingSource::Dataset {
+            typed_operations.push(Arc::clone(operation));
+            continue;
+        }
+
+        let mut column_types = None;
+
+        let table = operation_settings
+            .schema_table
+            .as_ref()
+            .or(smartness_settings.schema_table.as_ref());
+        if let (Some(keyspace), Some(table)) = (smartness_settings.schema_keyspace.as_ref(), table)
+        {
+            if !schema_column_types.contains_key(table) {
+                let table_column_types = load_table_column_types(&session, keyspace, table).await?;
+                schema_column_types.insert(table.clone(), table_column_types);
+            }
+            column_types = schema_column_types.get(table).cloned();
+        }
+
+        for settings_column_types in [
+            smartness_settings.column_types.as_ref(),
+            operation_settings.column_types.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            column_types
+                .get_or_insert_with(HashMap::new)
+                .extend(settings_column_types.clone());
+        }
+
+        typed_operations.push(Arc::new(CsqlOperation {
+            column_types,
+            ..CsqlOperation::clone(operation)
+        }));
+    }
+
+    Ok(typed_operations)
+}
+
+async fn load_table_column_types(
+    session: &Session,
+    keyspace: &str,
+    table: &str,
+) -> Result<HashMap<String, String>, SmartnessError> {
+    let rows_result = session
+        .query_unpaged(
+            "SELECT column_name, type FROM system_schema.columns WHERE keyspace_name = ? AND table_name = ?",
+            (keyspace, table),
+        )
+        .await
+        .map_err(|err| SmartnessError::CsqlLoadColumnTypesError(Box::new(err)))?
+        .into_rows_result()
+        .map_err(|err| SmartnessError::CsqlColumnTypesRowsError(Box::new(err)))?;
+
+    let mut column_types = HashMap::new();
+    for row in rows_result
+        .rows::<(String, String)>()
+        .map_err(SmartnessError::CsqlColumnTypesTypeCheckError)?
+    {
+        let (column_name, column_type) =
+            row.map_err(SmartnessError::CsqlColumnTypesDeserializationError)?;
+        column_types.insert(column_name, column_type);
+    }
+
+    println!(
+        "Column types of {}.{} loaded from system_schema: {} columns.",
+        keyspace,
+        table,
+        column_types.len()
+    );
+
+    Ok(column_types)
+}
+
 // function that will apply write operations as a warmup step.
-// if warmup_enabled setting is true, it will run...
+// if warmup_enabled setting is true, it will run using an operation bound to dataset...
 pub async fn warmup_op(
     smartness_settings: &SmartnessSettings,
-    session: Arc<Session>,
+    operation: &CsqlOperation,
     dataset_file: File,
 ) -> Result<(), SmartnessError> {
     if smartness_settings.warmup_enabled.is_some() && smartness_settings.warmup_enabled.unwrap() {
-        println!("Warmup Operations started.");
+        println!(
+            "Warmup Operations started using operation {}.",
+            operation.name
+        );
 
-        let mut rdr = Reader::from_reader(dataset_file);
+        // records with missing fields are read, so the binder reports them...
+        let mut rdr = ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(dataset_file);
         let cols_qty = smartness_settings.cols_qty.unwrap();
 
-        {
+        let binder = {
             let empty_header = StringRecord::new();
-            let _headers = rdr.headers().unwrap_or(&empty_header);
-        }
+            let headers = rdr.headers().unwrap_or(&empty_header);
+            CsqlRowBinder::new(operation.column_types.as_ref(), headers, cols_qty)?
+        };
 
         let mut iter = rdr.into_records();
         let pos = iter.reader().position().clone();
 
         for _i in 0..smartness_settings.warmup_qty_ops.unwrap() {
             if let Some(record) = iter.next() {
-                if let Ok(record) = record {
-                    let uuid = Uuid::new_v4();
-
-                    let mut cql_values = Vec::<CqlValue>::new();
-                    cql_values.push(CqlValue::Uuid(uuid));
+                let record = match record {
+                    Ok(record) => record,
+                    Err(err) => {
+                        // a record that could not be read is skipped...
+                        println!("Error when read a dataset record: {}", err);
+                        continue;
+                    }
+                };
 
-                    let mut count_col = 0;
-                    for value in record.iter() {
-                        if cols_qty != -1 && count_col >= cols_qty {
-                            break;
-                        }
+                let mut cql_values = Vec::new();
+                operation.key_generator.generate(&mut cql_values);
+                let key = cql_values.clone();
 
-                        cql_values.push(CqlValue::Text(value.to_owned()));
+                binder.bind(&record, &mut cql_values)?;
 
-                        count_col += 1;
-                    }
+                // insert record
+                operation
+                    .statement
+                    .execute_unpaged(&operation.session, cql_values)
+                    .await
+                    .map_err(|err| SmartnessError::WarmupInsertOpError(Box::new(err)))?;
 
-                    // create table
-                    session
-                        .query_unpaged(
-                            smartness_settings.write_script.as_ref().unwrap().clone(),
-                            cql_values,
-                        )
-                        .await
-                        .map(|_| ())
-                        .map_err(SmartnessError::WarmupInsertOpError)?;
+                // warmup keys could be read by operations bound to key...
+                if let Some(key_pool) = &operation.key_pool {
+                    key_pool.add(key);
                 }
             } else {
                 if iter.reader_mut().seek(pos.clone()).is_ok() {
@@ -201,39 +447,64 @@ pub async fn warmup_op(
     Ok(())
 }
 
-// function that will send a write operation using write_script from settings
-pub async fn write_op<'a>(
-    session: Arc<Session>,
-    metrics_manager: Arc<MetricsManager>,
-    insert: &'a str,
+// function that will send an operation, binding values when it has them...
+// write operations are sent unpaged and read operations fetch a single page.
+// intended_start is the instant when the scheduler planned to start this operation.
+pub async fn execute_op(
+    operation: Arc<CsqlOperation>,
     values: Vec<CqlValue>,
+    intended_start: Instant,
 ) -> Result<(), SmartnessError> {
-    let request_start = std::time::Instant::now();
-    // execute write operation
-    session
-        .query_unpaged(insert, values)
-        .await
-        .map(|_| ())
-        .map_err(SmartnessError::CsqlWriteOpError)?;
-    let elapsed = request_start.elapsed();
-    metrics_manager.add_latency(elapsed.as_millis() as f64);
-    Ok(())
-}
+    // the generated key is bound before dataset values...
+    let written_key = if operation.writes_keys() {
+        values
+            .get(..operation.key_generator.key_len())
+            .map(<[CqlValue]>::to_vec)
+    } else {
+        None
+    };
 
-// function that will send a read operation using read_script from settings
-pub async fn read_op(
-    session: Arc<Session>,
-    metrics_manager: Arc<MetricsManager>,
-    read: &str,
-) -> Result<(), SmartnessError> {
-    let request_start = std::time::Instant::now();
-    // execute read operation
-    session
-        .query_single_page(read, (), PagingState::start())
-        .await
-        .map(|_| ())
-        .map_err(SmartnessError::CsqlReadOpError)?;
-    let elapsed = request_start.elapsed();
-    metrics_manager.add_latency(elapsed.as_millis() as f64);
-    Ok(())
+    let request_start = Instant::now();
+    // execute operation
+    let result = match operation.kind {
+        OperationKind::Write => {
+            operation
+                .statement
+                .execute_unpaged(&operation.session, values)
+                .await
+        }
+        OperationKind::Read => {
+            operation
+                .statement
+                .execute_single_page(&operation.session, values)
+                .await
+        }
+    };
+    // service time from request start and response time from scheduled start
+    let service_time = request_start.elapsed();
+    let response_time = intended_start.elapsed();
+    match result {
+        Ok(()) => {
+            operation
+                .metrics_manager
+                .add_latency(service_time, response_time);
+            // only keys of successful writes are read back...
+            if let (Some(key), Some(key_pool)) = (written_key, &operation.key_pool) {
+                key_pool.add(key);
+            }
+            Ok(())
+        }
+        Err(err) => {
+            // failed operations are counted by kind and have their own latencies...
+            operation.metrics_manager.add_error(
+                MetricsErrorKind::from(&err),
+                service_time,
+                response_time,
+            );
+            match operation.kind {
+                OperationKind::Write => Err(SmartnessError::CsqlWriteOpError(Box::new(err))),
+                OperationKind::Read => Err(SmartnessError::CsqlReadOpError(Box::new(err))),
+            }
+        }
+    }
 }
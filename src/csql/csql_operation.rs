@@ -0,0 +1,106 @@
+use std::{collections::HashMap, sync::Arc};
+
+use scylla::client::session::Session;
+
+use crate::{
+    config::smarteness_settings::{BindingSource, OperationKind},
+    csql::{
+        csql_key_generator::CsqlKeyGenerator, csql_key_pool::CsqlKeyPool, csql_op::CsqlStatement,
+    },
+    metrics::metrics_manager::MetricsManager,
+};
+
+/// an operation from workload with its session, statement and metrics
+#[derive(Clone)]
+pub struct CsqlOperation {
+    pub name: String,
+    pub kind: OperationKind,
+    pub binding: BindingSource,
+    pub weight: u32,
+    pub session: Arc<Session>,
+    pub statement: CsqlStatement,
+    /// types used to bind dataset columns, None if they are bound as text
+    pub column_types: Option<HashMap<String, String>>,
+    pub metrics_manager: Arc<MetricsManager>,
+    /// key generator shared by all operations
+    pub key_generator: Arc<CsqlKeyGenerator>,
+    /// keys written by this operation if it writes keys, or read by it if it is bound to key
+    pub key_pool: Option<Arc<CsqlKeyPool>>,
+}
+
+impl CsqlOperation {
+    /// write operations bound to dataset generate keys, so they are kept in key pool
+    pub fn writes_keys(&self) -> bool {
+        self.kind == OperationKind::Write && self.binding == BindingSource::Dataset
+    }
+}
+
+/// picks operations by weight using smooth weighted round robin,
+/// so operations are interleaved instead of sent in bursts.
+pub struct CsqlOperationPicker {
+    weights: Vec<i64>,
+    current_weights: Vec<i64>,
+    total_weight: i64,
+}
+
+impl CsqlOperationPicker {
+    /// weights in the same order of operations
+    pub fn new(weights: &[u32]) -> Self {
+        let weights: Vec<i64> = weights.iter().map(|weight| *weight as i64).collect();
+
+        CsqlOperationPicker {
+            current_weights: vec![0; weights.len()],
+            total_weight: weights.iter().sum(),
+            weights,
+        }
+    }
+
+    /// index of the next operation
+    pub fn pick(&mut self) -> usize {
+        let mut selected = 0;
+        for (index, weight) in self.weights.iter().enumerate() {
+            self.current_weights[index] += weight;
+            if self.current_weights[index] > self.current_weights[selected] {
+                selected = index;
+            }
+        }
+
+        self.current_weights[selected] -= self.total_weight;
+        selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picks(weights: &[u32], count: usize) -> Vec<usize> {
+        let mut picker = CsqlOperationPicker::new(weights);
+        (0..count).map(|_| picker.pick()).collect()
+    }
+
+    #[test]
+    fn pick_follows_weights_in_every_round() {
+        let weights = [5, 3, 2];
+        let picks = picks(&weights, 1000);
+
+        // each round of total weight picks every operation exactly by its weight...
+        for round in picks.chunks(10) {
+            for (index, weight) in weights.iter().enumerate() {
+                let count = round.iter().filter(|pick| **pick == index).count();
+                assert_eq!(count, *weight as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn pick_interleaves_operations() {
+        assert_eq!(picks(&[1, 1], 4), vec![0, 1, 0, 1]);
+        assert_eq!(picks(&[5, 1, 1], 7), vec![0, 0, 1, 0, 2, 0, 0]);
+    }
+
+    #[test]
+    fn pick_skips_operations_without_weight() {
+        assert!(picks(&[0, 3, 0], 30).iter().all(|pick| *pick == 1));
+    }
+}
@@ -0,0 +1,371 @@
+use std::{collections::HashMap, net::IpAddr, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use csv::StringRecord;
+use num_bigint::BigInt;
+use scylla::value::{CqlDate, CqlDecimal, CqlTime, CqlTimestamp, CqlTimeuuid, CqlValue, CqlVarint};
+use uuid::Uuid;
+
+use crate::error::SmartnessError;
+
+const TIMESTAMP_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%d/%m/%Y %H:%M:%S",
+];
+
+/// CQL native types that could be bound from a dataset field
+#[derive(Clone, Copy, Debug)]
+pub enum CsqlColumnType {
+    Ascii,
+    BigInt,
+    Blob,
+    Boolean,
+    Date,
+    Decimal,
+    Double,
+    Float,
+    Inet,
+    Int,
+    SmallInt,
+    Text,
+    Time,
+    Timestamp,
+    Timeuuid,
+    TinyInt,
+    Uuid,
+    Varint,
+}
+
+impl CsqlColumnType {
+    /// parse a type name as returned by system_schema.columns or set in column_types
+    pub fn parse(cql_type: &str) -> Result<Self, SmartnessError> {
+        let column_type = match cql_type.trim().to_lowercase().as_str() {
+            "ascii" => CsqlColumnType::Ascii,
+            "bigint" => CsqlColumnType::BigInt,
+            "blob" => CsqlColumnType::Blob,
+            "boolean" => CsqlColumnType::Boolean,
+            "date" => CsqlColumnType::Date,
+            "decimal" => CsqlColumnType::Decimal,
+            "double" => CsqlColumnType::Double,
+            "float" => CsqlColumnType::Float,
+            "inet" => CsqlColumnType::Inet,
+            "int" => CsqlColumnType::Int,
+            "smallint" => CsqlColumnType::SmallInt,
+            "text" | "varchar" => CsqlColumnType::Text,
+            "time" => CsqlColumnType::Time,
+            "timestamp" => CsqlColumnType::Timestamp,
+            "timeuuid" => CsqlColumnType::Timeuuid,
+            "tinyint" => CsqlColumnType::TinyInt,
+            "uuid" => CsqlColumnType::Uuid,
+            "varint" => CsqlColumnType::Varint,
+            _ => {
+                return Err(SmartnessError::CsqlUnsupportedColumnType(
+                    cql_type.to_owned(),
+                ));
+            }
+        };
+
+        Ok(column_type)
+    }
+
+    /// convert a dataset field into a CqlValue, returning None when the field is not valid.
+    fn convert(&self, value: &str) -> Option<CqlValue> {
+        // empty fields are sent as empty values, except for text columns...
+        if value.is_empty() && !matches!(self, CsqlColumnType::Ascii | CsqlColumnType::Text) {
+            return Some(CqlValue::Empty);
+        }
+
+        let cql_value = match self {
+            CsqlColumnType::Ascii => CqlValue::Ascii(value.to_owned()),
+            CsqlColumnType::BigInt => CqlValue::BigInt(value.trim().parse().ok()?),
+            CsqlColumnType::Blob => CqlValue::Blob(decode_hex(value.trim())?),
+            CsqlColumnType::Boolean => CqlValue::Boolean(parse_bool(value.trim())?),
+            CsqlColumnType::Date => {
+                let date = NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").ok()?;
+                CqlValue::Date(CqlDate::from(date))
+            }
+            CsqlColumnType::Decimal => {
+                let decimal = BigDecimal::from_str(value.trim()).ok()?;
+                CqlValue::Decimal(CqlDecimal::try_from(decimal).ok()?)
+            }
+            CsqlColumnType::Double => CqlValue::Double(value.trim().parse().ok()?),
+            CsqlColumnType::Float => CqlValue::Float(value.trim().parse().ok()?),
+            CsqlColumnType::Inet => CqlValue::Inet(IpAddr::from_str(value.trim()).ok()?),
+            CsqlColumnType::Int => CqlValue::Int(value.trim().parse().ok()?),
+            CsqlColumnType::SmallInt => CqlValue::SmallInt(value.trim().parse().ok()?),
+            CsqlColumnType::Text => CqlValue::Text(value.to_owned()),
+            CsqlColumnType::Time => {
+                let time = NaiveTime::parse_from_str(value.trim(), "%H:%M:%S%.f").ok()?;
+                CqlValue::Time(CqlTime::try_from(time).ok()?)
+            }
+            CsqlColumnType::Timestamp => CqlValue::Timestamp(parse_timestamp(value.trim())?),
+            CsqlColumnType::Timeuuid => {
+                CqlValue::Timeuuid(CqlTimeuuid::from_str(value.trim()).ok()?)
+            }
+            CsqlColumnType::TinyInt => CqlValue::TinyInt(value.trim().parse().ok()?),
+            CsqlColumnType::Uuid => CqlValue::Uuid(Uuid::parse_str(value.trim()).ok()?),
+            CsqlColumnType::Varint => {
+                let varint = BigInt::from_str(value.trim()).ok()?;
+                CqlValue::Varint(CqlVarint::from(varint))
+            }
+        };
+
+        Some(cql_value)
+    }
+}
+
+/// binds dataset records to CqlValues using the type of each dataset column
+pub struct CsqlRowBinder {
+    columns: Vec<(String, CsqlColumnType)>,
+}
+
+impl CsqlRowBinder {
+    /// create a binder for the first cols_qty dataset headers...
+    /// if column_types is None, all columns will be bound as text.
+    pub fn new(
+        column_types: Option<&HashMap<String, String>>,
+        headers: &StringRecord,
+        cols_qty: i64,
+    ) -> Result<Self, SmartnessError> {
+        let mut columns = Vec::new();
+
+        for (count_col, header) in headers.iter().enumerate() {
+            if cols_qty != -1 && count_col as i64 >= cols_qty {
+                break;
+            }
+
+            let column_type = match column_types {
+                Some(column_types) => {
+                    CsqlColumnType::parse(column_types.get(header).ok_or_else(|| {
+                        SmartnessError::CsqlColumnTypeNotFound(header.to_owned())
+                    })?)?
+                }
+                None => CsqlColumnType::Text,
+            };
+
+            columns.push((header.to_owned(), column_type));
+        }
+
+        Ok(Self { columns })
+    }
+
+    /// append record fields converted to CqlValues into cql_values
+    pub fn bind(
+        &self,
+        record: &StringRecord,
+        cql_values: &mut Vec<CqlValue>,
+    ) -> Result<(), SmartnessError> {
+        // fields after the first cols_qty are not bound, but a record must have all bound ones
+        if record.len() < self.columns.len() {
+            return Err(SmartnessError::CsqlRecordLengthError {
+                line: record.position().map_or(0, |position| position.line()),
+                expected: self.columns.len(),
+                found: record.len(),
+            });
+        }
+
+        for ((column, column_type), value) in self.columns.iter().zip(record.iter()) {
+            let cql_value = column_type.convert(value).ok_or_else(|| {
+                SmartnessError::CsqlValueConversionError {
+                    column: column.clone(),
+                    cql_type: format!("{:?}", column_type),
+                    value: value.to_owned(),
+                }
+            })?;
+
+            cql_values.push(cql_value);
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "1" | "yes" => Some(true),
+        "false" | "0" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_timestamp(value: &str) -> Option<CqlTimestamp> {
+    // milliseconds since unix epoch
+    if let Ok(millis) = value.parse::<i64>() {
+        return Some(CqlTimestamp(millis));
+    }
+
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Some(CqlTimestamp::from(date_time.with_timezone(&Utc)));
+    }
+
+    TIMESTAMP_FORMATS.iter().find_map(|format| {
+        NaiveDateTime::parse_from_str(value, format)
+            .ok()
+            .map(|date_time| CqlTimestamp::from(date_time.and_utc()))
+    })
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    let value = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binder(types: &[(&str, &str)]) -> CsqlRowBinder {
+        let column_types: HashMap<String, String> = types
+            .iter()
+            .map(|(column, cql_type)| (column.to_string(), cql_type.to_string()))
+            .collect();
+        let headers =
+            StringRecord::from(types.iter().map(|(column, _)| *column).collect::<Vec<_>>());
+
+        CsqlRowBinder::new(Some(&column_types), &headers, -1).unwrap()
+    }
+
+    #[test]
+    fn bind_converts_fields_by_column_type() {
+        let binder = binder(&[
+            ("id", "int"),
+            ("price", "decimal"),
+            ("active", "boolean"),
+            ("created", "timestamp"),
+            ("data", "blob"),
+            ("name", "text"),
+        ]);
+        let record = StringRecord::from(vec![
+            " 42 ",
+            "10.50",
+            "yes",
+            "1970-01-01T00:00:01Z",
+            "0xCAFE",
+            "",
+        ]);
+
+        let mut cql_values = Vec::new();
+        binder.bind(&record, &mut cql_values).unwrap();
+
+        assert_eq!(
+            cql_values,
+            vec![
+                CqlValue::Int(42),
+                CqlValue::Decimal(
+                    CqlDecimal::try_from(BigDecimal::from_str("10.50").unwrap()).unwrap()
+                ),
+                CqlValue::Boolean(true),
+                CqlValue::Timestamp(CqlTimestamp(1000)),
+                CqlValue::Blob(vec![0xca, 0xfe]),
+                CqlValue::Text(String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn bind_sends_empty_non_text_fields_as_empty_values() {
+        let binder = binder(&[("id", "bigint")]);
+
+        let mut cql_values = Vec::new();
+        binder
+            .bind(&StringRecord::from(vec![""]), &mut cql_values)
+            .unwrap();
+
+        assert_eq!(cql_values, vec![CqlValue::Empty]);
+    }
+
+    #[test]
+    fn bind_rejects_invalid_fields() {
+        let binder = binder(&[("id", "int")]);
+
+        let err = binder
+            .bind(&StringRecord::from(vec!["forty"]), &mut Vec::new())
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            SmartnessError::CsqlValueConversionError { column, value, .. }
+                if column == "id" && value == "forty"
+        ));
+    }
+
+    #[test]
+    fn bind_rejects_records_with_missing_fields() {
+        let binder = binder(&[("id", "int"), ("name", "text")]);
+
+        let err = binder
+            .bind(&StringRecord::from(vec!["1"]), &mut Vec::new())
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            SmartnessError::CsqlRecordLengthError {
+                expected: 2,
+                found: 1,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn bind_reports_line_of_short_records_read_by_a_flexible_reader() {
+        let dataset = "id,name\n1,a\n2\n";
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(dataset.as_bytes());
+        let binder = CsqlRowBinder::new(None, reader.headers().unwrap(), -1).unwrap();
+
+        let records: Vec<StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert!(binder.bind(&records[0], &mut Vec::new()).is_ok());
+        assert!(matches!(
+            binder.bind(&records[1], &mut Vec::new()),
+            Err(SmartnessError::CsqlRecordLengthError {
+                line: 3,
+                expected: 2,
+                found: 1,
+            })
+        ));
+    }
+
+    #[test]
+    fn new_binds_only_first_cols_qty_columns() {
+        let headers = StringRecord::from(vec!["id", "name", "extra"]);
+        let binder = CsqlRowBinder::new(None, &headers, 2).unwrap();
+
+        let mut cql_values = Vec::new();
+        binder
+            .bind(&StringRecord::from(vec!["1", "a", "b"]), &mut cql_values)
+            .unwrap();
+
+        assert_eq!(
+            cql_values,
+            vec![
+                CqlValue::Text("1".to_owned()),
+                CqlValue::Text("a".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_rejects_unsupported_types() {
+        assert!(CsqlColumnType::parse("VARCHAR").is_ok());
+        assert!(matches!(
+            CsqlColumnType::parse("list<int>"),
+            Err(SmartnessError::CsqlUnsupportedColumnType(_))
+        ));
+    }
+}
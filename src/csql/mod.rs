@@ -1 +1,7 @@
+pub mod csql_auth;
+pub mod csql_key_generator;
+pub mod csql_key_pool;
 pub mod csql_op;
+pub mod csql_operation;
+pub mod csql_tls;
+pub mod csql_types;
@@ -1,4 +1,7 @@
-use scylla::errors::{ExecutionError, NewSessionError};
+use scylla::errors::{
+    DeserializationError, ExecutionError, IntoRowsResultError, NewSessionError, PrepareError,
+    RowsError,
+};
 use std::{error::Error, fmt::Debug};
 use thiserror::Error;
 
@@ -14,6 +17,16 @@ pub enum SmartnessError {
     MetricsFileCreateError(#[source] std::io::Error),
     #[error("failed to write headers into metrics file")]
     MetricsFileWriteHeadersError(#[source] csv::Error),
+    #[error("failed to write a record into metrics file")]
+    MetricsFileWriteError(#[source] std::io::Error),
+    #[error("failed to write parquet metrics file")]
+    MetricsParquetError(#[source] parquet::errors::ParquetError),
+    #[error("failed to listen Prometheus metrics address")]
+    MetricsPrometheusBindError(#[source] std::io::Error),
+    #[error("failed to create run summary file")]
+    MetricsReportCreateError(#[source] std::io::Error),
+    #[error("failed to write run summary file")]
+    MetricsReportWriteError(#[source] serde_json::Error),
     #[error("failed to open dataset file")]
     DatasetFileOpenError(#[source] std::io::Error),
     #[error("dataset file does not exist")]
@@ -26,36 +39,172 @@ pub enum SmartnessError {
     WorkloadFileDeserializationError(#[source] serde_json::Error),
     #[error("it is required set cycles or running_time")]
     CyclesOrRunningTimeRequired,
-    #[error("it is required set write_script")]
+    #[error("it is required set write_script or operations")]
     WriteScriptRequired,
-    #[error("it is required set read_script")]
+    #[error("it is required set read_script or operations")]
     ReadScriptRequired,
+    #[error("it is required set at least one operation in operations")]
+    OperationsRequired,
+    #[error("operation name {0} is not valid, use only letters, digits, _ and -")]
+    OperationNameInvalid(String),
+    #[error("operation name {0} is duplicated")]
+    OperationNameDuplicated(String),
+    #[error("it is required set schema_keyspace to use schema_table of operation {0}")]
+    OperationSchemaKeyspaceRequired(String),
+    #[error("it is required set weight greater than 0 in at least one operation")]
+    OperationsWeightRequired,
+    #[error("operation {0} is bound to key, it is required a write operation bound to dataset")]
+    OperationKeyWriterRequired(String),
+    #[error(
+        "operation {0} is bound to key, it is required set keys_from because there are many write operations bound to dataset"
+    )]
+    OperationKeysFromRequired(String),
+    #[error("keys_from {1} of operation {0} is not a write operation bound to dataset")]
+    OperationKeysFromInvalid(String, String),
+    #[error("tasks_per_sec must be greater than 0")]
+    TasksPerSecInvalid,
+    #[error(
+        "load_profile rates must be greater than 0, sinusoid amplitude lower than tasks_per_sec and flash crowd shock_level greater than 1"
+    )]
+    LoadProfileRateInvalid,
+    #[error("load_profile minutes must be greater than 0 and start_minutes must not be negative")]
+    LoadProfileMinutesInvalid,
+    #[error("it is required set clients greater than 0 in closed mode")]
+    ClientsRequired,
+    #[error("max_in_flight must be greater than 0")]
+    MaxInFlightInvalid,
+    #[error("partitions of composite key_generator must be greater than 0")]
+    KeyGeneratorPartitionsInvalid,
+    #[error("key_pool_size must be greater than 0")]
+    KeyPoolSizeInvalid,
+    #[error("key_zipfian_exponent must be greater than 0")]
+    KeyZipfianExponentInvalid,
+    #[error("it is required set at least one phase in phases")]
+    PhasesRequired,
+    #[error("phase name {0} is not valid, use only letters, digits, _ and -")]
+    PhaseNameInvalid(String),
+    #[error("phase name {0} is duplicated")]
+    PhaseNameDuplicated(String),
+    #[error("it is required set cycles or running_time in phase {0}")]
+    PhaseCyclesOrRunningTimeRequired(String),
+    #[error("reads_rate of phase {0} must be between 0 and 1")]
+    PhaseReadsRateInvalid(String),
+    #[error("phase {0} sets weight of operation {1} that does not exist")]
+    PhaseOperationNotFound(String, String),
+    #[error("it is required a weight greater than 0 in at least one operation of phase {0}")]
+    PhaseWeightRequired(String),
     #[error(
         "it is required set startup_create_schema_script and startup_drop_table_script and startup_create_table_script"
     )]
     StartuptScriptsRequired,
+    #[error("it is required set schema_keyspace and schema_table together")]
+    SchemaKeyspaceAndTableRequired,
     #[error("it is required set warmup_qty_ops")]
     WarmupQtyOpsRequired,
-    #[error("it is required set cassandra_host")]
+    #[error(
+        "consistency {0} is not valid, use ANY, ONE, TWO, THREE, QUORUM, ALL, LOCAL_QUORUM, EACH_QUORUM, LOCAL_ONE, SERIAL or LOCAL_SERIAL"
+    )]
+    ConsistencyInvalid(String),
+    #[error("serial consistency {0} is not valid, use SERIAL or LOCAL_SERIAL")]
+    SerialConsistencyInvalid(String),
+    #[error("consistency {0} is not allowed in write_consistency, use serial_consistency instead")]
+    WriteConsistencyNotAllowed(String),
+    #[error("consistency {0} is not allowed in read_consistency")]
+    ReadConsistencyNotAllowed(String),
+    #[error("metrics_interval_ms must be greater than 0")]
+    MetricsIntervalInvalid,
+    #[error("metrics percentile {0} must be greater than 0 and lower or equal to 100")]
+    MetricsPercentileInvalid(f64),
+    #[error("metrics percentile {0} is duplicated")]
+    MetricsPercentileDuplicated(f64),
+    #[error("it is required set at least one sink in metrics_sinks")]
+    MetricsSinksRequired,
+    #[error("metrics sink {0} is duplicated")]
+    MetricsSinkDuplicated(String),
+    #[error("metrics_prometheus_address {0} is not valid, use ip:port")]
+    MetricsPrometheusAddressInvalid(String),
+    #[error("metrics_window_size_minutes must be greater than 0")]
+    MetricsWindowSizeInvalid,
+    #[error("metrics_histogram_precision must be between 0 and 5")]
+    MetricsHistogramPrecisionInvalid,
+    #[error("it is required set at least one node in cassandra_contact_points")]
+    CassandraContactPointsRequired,
+    #[error(
+        "it is required set local_datacenter with dc_aware_round_robin and rack_aware policies"
+    )]
+    LocalDatacenterRequired,
+    #[error("exclusion_threshold of latency_aware policy must be greater or equal to 1")]
+    LatencyAwareThresholdInvalid,
+    #[error("it is required set ca_cert_path in tls")]
+    TlsCaCertRequired,
+    #[error("it is required set client_cert_path and client_key_path together in tls")]
+    TlsClientCertAndKeyRequired,
+    #[error("failed to load CA certificates from {0}")]
+    TlsCaCertLoadError(String, #[source] rustls::pki_types::pem::Error),
+    #[error("failed to add CA certificate from {0} to trusted roots")]
+    TlsCaCertInvalid(String, #[source] rustls::Error),
+    #[error("failed to build node certificate verifier with CA certificates")]
+    TlsVerifierBuildError(#[source] rustls::client::VerifierBuilderError),
+    #[error("failed to load client certificates from {0}")]
+    TlsClientCertLoadError(String, #[source] rustls::pki_types::pem::Error),
+    #[error("failed to load client private key from {0}")]
+    TlsClientKeyLoadError(String, #[source] rustls::pki_types::pem::Error),
+    #[error("failed to create TLS configuration")]
+    TlsConfigError(#[source] rustls::Error),
+    #[error("it is required set cassandra_host or cassandra_contact_points")]
     CassandraHostRequired,
     #[error("it is required set cassandra_port")]
     CassandraPortRequired,
-    #[error("it is required set cassandra_username and cassandra_password")]
+    #[error("it is required set cassandra_username and cassandra_password together")]
     CassandraUsernameAndPasswordAreRequired,
+    #[error(
+        "it is required set cassandra_username and cassandra_password to use cassandra_authenticator"
+    )]
+    CassandraAuthenticatorCredentialsRequired,
+    #[error("failed to open credentials file {0}")]
+    CredentialsFileOpenError(String, #[source] std::io::Error),
+    #[error("failed to deserialize credentials file {0}")]
+    CredentialsFileDeserializationError(String, #[source] serde_json::Error),
     #[error("error when create a ScyllaDB session")]
     ScyllaSessionError(#[source] NewSessionError),
     #[error("error when run create keyspace script")]
-    CsqlCreateKeyspaceError(#[source] ExecutionError),
+    CsqlCreateKeyspaceError(#[source] Box<ExecutionError>),
     #[error("error when run drop table script")]
-    CsqlDropTableError(#[source] ExecutionError),
+    CsqlDropTableError(#[source] Box<ExecutionError>),
     #[error("error when run create table script")]
-    CsqlCreateTableError(#[source] ExecutionError),
+    CsqlCreateTableError(#[source] Box<ExecutionError>),
+    #[error("error when prepare script of operation {0}")]
+    CsqlPrepareOperationError(String, #[source] Box<PrepareError>),
+    #[error("error when load column types from system_schema.columns")]
+    CsqlLoadColumnTypesError(#[source] Box<ExecutionError>),
+    #[error("error when read column types rows from system_schema.columns")]
+    CsqlColumnTypesRowsError(#[source] Box<IntoRowsResultError>),
+    #[error("error when type check column types rows from system_schema.columns")]
+    CsqlColumnTypesTypeCheckError(#[source] RowsError),
+    #[error("error when deserialize column types rows from system_schema.columns")]
+    CsqlColumnTypesDeserializationError(#[source] DeserializationError),
+    #[error("column type not found for dataset column {0}")]
+    CsqlColumnTypeNotFound(String),
+    #[error("column type {0} is not supported")]
+    CsqlUnsupportedColumnType(String),
+    #[error("failed to convert value '{value}' of column {column} to {cql_type}")]
+    CsqlValueConversionError {
+        column: String,
+        cql_type: String,
+        value: String,
+    },
+    #[error("dataset record at line {line} has {found} fields, expected at least {expected}")]
+    CsqlRecordLengthError {
+        line: u64,
+        expected: usize,
+        found: usize,
+    },
     #[error("error when insert a record via warmup")]
-    WarmupInsertOpError(#[source] ExecutionError),
+    WarmupInsertOpError(#[source] Box<ExecutionError>),
     #[error("error when execute a write operation")]
-    CsqlWriteOpError(#[source] ExecutionError),
+    CsqlWriteOpError(#[source] Box<ExecutionError>),
     #[error("error when execute a read operation")]
-    CsqlReadOpError(#[source] ExecutionError),
+    CsqlReadOpError(#[source] Box<ExecutionError>),
 }
 
 impl Debug for SmartnessError {
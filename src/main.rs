@@ -1,10 +1,14 @@
-use std::{fs::File, path::Path, time::Duration};
+use std::{fs::File, path::Path};
 
+use chrono::Utc;
 use clap::{ArgAction, Parser};
 use error::SmartnessError;
 
-use crate::config::{
-    metrics_runtime, process_runtime::ProcessRuntime, smarteness_settings::SmartnessSettings,
+use crate::{
+    config::{
+        metrics_runtime, process_runtime::ProcessRuntime, smarteness_settings::SmartnessSettings,
+    },
+    metrics::metrics_report::RunReport,
 };
 
 mod config;
@@ -40,8 +44,9 @@ fn main() -> Result<(), SmartnessError> {
     let dataset_file = File::open(dataset_path).map_err(SmartnessError::DatasetFileOpenError)?;
 
     // Process runtime
-    let process_runtime = ProcessRuntime::new(&smartness_settings, dataset_file)?;
+    let mut process_runtime = ProcessRuntime::new(&smartness_settings, dataset_file)?;
     process_runtime.handle_startup()?;
+    process_runtime.handle_prepare()?;
     {
         let dataset_file_warmup =
             File::open(dataset_path).map_err(SmartnessError::DatasetFileOpenError)?;
@@ -49,25 +54,37 @@ fn main() -> Result<(), SmartnessError> {
     }
 
     // Metrics runtime
+    let file_name = metrics_runtime::metrics_file_name();
     let mut metrics_runtime = None;
     if !args.no_metrics {
         metrics_runtime = Some(metrics_runtime::create_runtime(
             &smartness_settings,
-            process_runtime.write_session.clone(),
-            process_runtime.write_metrics_manager.clone(),
-            process_runtime.read_session.clone(),
-            process_runtime.read_metrics_manager.clone(),
+            &file_name,
+            &process_runtime.operations,
+            process_runtime.current_phase.clone(),
+            process_runtime.rate_meter.clone(),
         )?);
     }
 
+    let started_at = Utc::now();
     process_runtime.start_runtime()?;
+    let run_report = RunReport::new(
+        &smartness_settings,
+        &process_runtime.operations,
+        &process_runtime.rate_meter,
+        started_at,
+        Utc::now(),
+    );
     process_runtime.shutdown();
     println!("Process Runtime stopped.");
 
     if let Some(metrics_runtime) = metrics_runtime {
-        metrics_runtime.shutdown_timeout(Duration::from_secs(2));
+        metrics_runtime.shutdown();
         println!("Metrics Runtime stopped.");
     }
 
+    run_report.print();
+    run_report.save(&smartness_settings.metrics_dir, &file_name)?;
+
     Ok(())
 }
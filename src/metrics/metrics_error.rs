@@ -0,0 +1,164 @@
+use scylla::errors::{DbError, ExecutionError, RequestAttemptError};
+
+/// kinds of failed operations counted by MetricsManager
+#[derive(Clone, Copy, Debug)]
+pub enum MetricsErrorKind {
+    Timeout,
+    Unavailable,
+    Overloaded,
+    WriteTimeout,
+    ReadTimeout,
+    Syntax,
+    Connection,
+    Other,
+}
+
+impl MetricsErrorKind {
+    pub const ALL: [MetricsErrorKind; 8] = [
+        MetricsErrorKind::Timeout,
+        MetricsErrorKind::Unavailable,
+        MetricsErrorKind::Overloaded,
+        MetricsErrorKind::WriteTimeout,
+        MetricsErrorKind::ReadTimeout,
+        MetricsErrorKind::Syntax,
+        MetricsErrorKind::Connection,
+        MetricsErrorKind::Other,
+    ];
+
+    /// name used in metrics columns
+    pub fn name(&self) -> &'static str {
+        match self {
+            MetricsErrorKind::Timeout => "timeout",
+            MetricsErrorKind::Unavailable => "unavailable",
+            MetricsErrorKind::Overloaded => "overloaded",
+            MetricsErrorKind::WriteTimeout => "write_timeout",
+            MetricsErrorKind::ReadTimeout => "read_timeout",
+            MetricsErrorKind::Syntax => "syntax",
+            MetricsErrorKind::Connection => "connection",
+            MetricsErrorKind::Other => "other",
+        }
+    }
+}
+
+impl From<&ExecutionError> for MetricsErrorKind {
+    fn from(error: &ExecutionError) -> Self {
+        match error {
+            ExecutionError::RequestTimeout(_) => MetricsErrorKind::Timeout,
+            ExecutionError::EmptyPlan | ExecutionError::ConnectionPoolError(_) => {
+                MetricsErrorKind::Connection
+            }
+            ExecutionError::LastAttemptError(attempt_error) => match attempt_error {
+                RequestAttemptError::DbError(db_error, _) => match db_error {
+                    DbError::Unavailable { .. } => MetricsErrorKind::Unavailable,
+                    DbError::Overloaded | DbError::RateLimitReached { .. } => {
+                        MetricsErrorKind::Overloaded
+                    }
+                    DbError::WriteTimeout { .. } => MetricsErrorKind::WriteTimeout,
+                    DbError::ReadTimeout { .. } => MetricsErrorKind::ReadTimeout,
+                    DbError::SyntaxError | DbError::Invalid => MetricsErrorKind::Syntax,
+                    _ => MetricsErrorKind::Other,
+                },
+                RequestAttemptError::BrokenConnectionError(_)
+                | RequestAttemptError::UnableToAllocStreamId => MetricsErrorKind::Connection,
+                _ => MetricsErrorKind::Other,
+            },
+            _ => MetricsErrorKind::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use scylla::{
+        errors::{ConnectionPoolError, OperationType, WriteType},
+        statement::Consistency,
+    };
+
+    use super::*;
+
+    fn db_error_kind(db_error: DbError) -> &'static str {
+        let error = ExecutionError::LastAttemptError(RequestAttemptError::DbError(
+            db_error,
+            "error".to_owned(),
+        ));
+        MetricsErrorKind::from(&error).name()
+    }
+
+    #[test]
+    fn request_errors_are_classified() {
+        let timeout = ExecutionError::RequestTimeout(Duration::from_secs(1));
+        assert_eq!(MetricsErrorKind::from(&timeout).name(), "timeout");
+
+        for error in [
+            ExecutionError::EmptyPlan,
+            ExecutionError::ConnectionPoolError(ConnectionPoolError::Initializing),
+            ExecutionError::LastAttemptError(RequestAttemptError::UnableToAllocStreamId),
+        ] {
+            assert_eq!(MetricsErrorKind::from(&error).name(), "connection");
+        }
+    }
+
+    #[test]
+    fn db_errors_are_classified() {
+        assert_eq!(
+            db_error_kind(DbError::Unavailable {
+                consistency: Consistency::Quorum,
+                required: 2,
+                alive: 1,
+            }),
+            "unavailable"
+        );
+        assert_eq!(db_error_kind(DbError::Overloaded), "overloaded");
+        assert_eq!(
+            db_error_kind(DbError::RateLimitReached {
+                op_type: OperationType::Write,
+                rejected_by_coordinator: true,
+            }),
+            "overloaded"
+        );
+        assert_eq!(
+            db_error_kind(DbError::WriteTimeout {
+                consistency: Consistency::Quorum,
+                received: 1,
+                required: 2,
+                write_type: WriteType::Simple,
+            }),
+            "write_timeout"
+        );
+        assert_eq!(
+            db_error_kind(DbError::ReadTimeout {
+                consistency: Consistency::Quorum,
+                received: 1,
+                required: 2,
+                data_present: false,
+            }),
+            "read_timeout"
+        );
+        assert_eq!(db_error_kind(DbError::SyntaxError), "syntax");
+        assert_eq!(db_error_kind(DbError::Invalid), "syntax");
+        assert_eq!(db_error_kind(DbError::TruncateError), "other");
+    }
+
+    #[test]
+    fn names_follow_all_order() {
+        let names: Vec<&str> = MetricsErrorKind::ALL
+            .iter()
+            .map(MetricsErrorKind::name)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "timeout",
+                "unavailable",
+                "overloaded",
+                "write_timeout",
+                "read_timeout",
+                "syntax",
+                "connection",
+                "other",
+            ]
+        );
+    }
+}
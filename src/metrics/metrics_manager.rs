@@ -1,51 +1,126 @@
-use std::sync::{
-    Mutex,
-    atomic::{AtomicU64, Ordering},
+use std::{
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
 };
 
-use statrs::statistics::{Data, Distribution, Max, Min, OrderStatistics};
+use hdrhistogram::Histogram;
+use serde::Serialize;
 
-use crate::{config::smarteness_settings::SmartnessSettings, metrics::metrics_store::MetricsStore};
+use crate::{
+    config::smarteness_settings::{LatencyUnit, SmartnessSettings},
+    metrics::{
+        metrics_error::MetricsErrorKind, metrics_recorder::ShardedRecorder,
+        metrics_store::MetricsStore,
+    },
+};
 
 const ORDER_TYPE: Ordering = Ordering::Relaxed;
 
-pub struct MetricSnapshot {
-    pub count: u64,
+/// upper bounds in seconds of latency buckets, the default buckets of Prometheus histograms
+pub const LATENCY_BUCKETS: [f64; 14] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Default, Serialize)]
+pub struct LatencyStats {
     pub min: f64,
     pub max: f64,
     pub mean: f64,
     pub std_dev: f64,
     pub median: f64,
-    pub p_75th: f64,
-    pub p_95th: f64,
-    pub p_98th: f64,
-    pub p_99th: f64,
-    pub p_99_9th: f64,
-    pub d_min: f64,
-    pub d_max: f64,
-    pub d_mean: f64,
-    pub d_std_dev: f64,
-    pub d_median: f64,
-    pub d_p_75th: f64,
-    pub d_p_95th: f64,
-    pub d_p_98th: f64,
-    pub d_p_99th: f64,
-    pub d_p_99_9th: f64,
-    pub w_min: f64,
-    pub w_max: f64,
-    pub w_mean: f64,
-    pub w_std_dev: f64,
-    pub w_median: f64,
-    pub w_p_75th: f64,
-    pub w_p_95th: f64,
-    pub w_p_98th: f64,
-    pub w_p_99th: f64,
-    pub w_p_99_9th: f64,
+    /// values of metrics_percentiles, in the same order
+    pub percentiles: Vec<PercentileValue>,
+}
+
+#[derive(Serialize)]
+pub struct PercentileValue {
+    /// percentile from 0 to 100, e.g. 99.9
+    pub percentile: f64,
+    pub value: f64,
+}
+
+impl LatencyStats {
+    /// get min, max, mean, std_dev, median and percentiles converted to latency_unit...
+    fn from_histogram(
+        histogram: &Histogram<u64>,
+        latency_unit: LatencyUnit,
+        percentiles: &[f64],
+    ) -> Self {
+        if histogram.is_empty() {
+            return LatencyStats {
+                percentiles: percentiles
+                    .iter()
+                    .map(|percentile| PercentileValue {
+                        percentile: *percentile,
+                        value: 0.0,
+                    })
+                    .collect(),
+                ..LatencyStats::default()
+            };
+        }
+
+        let quantile =
+            |quantile: f64| latency_unit.output_value(histogram.value_at_quantile(quantile) as f64);
+
+        LatencyStats {
+            min: latency_unit.output_value(histogram.min() as f64),
+            max: latency_unit.output_value(histogram.max() as f64),
+            mean: latency_unit.output_value(histogram.mean()),
+            std_dev: latency_unit.output_value(histogram.stdev()),
+            median: quantile(0.5),
+            percentiles: percentiles
+                .iter()
+                .map(|percentile| PercentileValue {
+                    percentile: *percentile,
+                    value: quantile(percentile / 100.0),
+                })
+                .collect(),
+        }
+    }
+}
+
+pub struct MetricSnapshot {
+    pub count: u64,
+    /// stats over all latencies
+    pub overall: LatencyStats,
+    /// quantity of all latencies lower or equal to each bound of LATENCY_BUCKETS, cumulative
+    pub overall_buckets: Vec<u64>,
+    /// stats over latencies since the last drain
+    pub drained: LatencyStats,
+    /// stats over latencies of the last metrics_window_size_minutes
+    pub windowed: LatencyStats,
+    /// quantity of latencies since the last metrics row
+    pub interval_count: u64,
+    /// stats over latencies since the last metrics row
+    pub interval: LatencyStats,
 }
 
 pub struct MetricsManager {
     pub count: AtomicU64,
+    /// latencies recorded by workers until they are merged into metrics stores
+    pub recorder: ShardedRecorder,
+    /// service time: from request start until its completion
     pub metrics_store: Mutex<MetricsStore>,
+    /// response time: from the instant scheduled to start the request until its completion
+    pub response_metrics_store: Mutex<MetricsStore>,
+    /// quantity of failed operations
+    pub failed_count: AtomicU64,
+    /// quantity of failed operations by MetricsErrorKind
+    pub error_counts: [AtomicU64; MetricsErrorKind::ALL.len()],
+    /// service time of failed operations
+    pub failed_metrics_store: Mutex<MetricsStore>,
+    /// response time of failed operations
+    pub failed_response_metrics_store: Mutex<MetricsStore>,
+    /// quantity of operations not sent because max_in_flight was reached
+    pub dropped_count: AtomicU64,
+    /// quantity of operations that waited because max_in_flight was reached
+    pub delayed_count: AtomicU64,
+    pub latency_unit: LatencyUnit,
+    /// percentiles of snapshots, from metrics_percentiles
+    pub percentiles: Vec<f64>,
     pub disabled: bool,
 }
 
@@ -53,197 +128,376 @@ impl MetricsManager {
     pub fn new(smartness_settings: &SmartnessSettings) -> Self {
         MetricsManager {
             count: AtomicU64::new(0),
+            recorder: ShardedRecorder::new(smartness_settings),
             metrics_store: Mutex::new(MetricsStore::new(smartness_settings)),
+            response_metrics_store: Mutex::new(MetricsStore::new(smartness_settings)),
+            failed_count: AtomicU64::new(0),
+            error_counts: Default::default(),
+            failed_metrics_store: Mutex::new(MetricsStore::new(smartness_settings)),
+            failed_response_metrics_store: Mutex::new(MetricsStore::new(smartness_settings)),
+            dropped_count: AtomicU64::new(0),
+            delayed_count: AtomicU64::new(0),
+            latency_unit: smartness_settings.metrics_latency_unit.unwrap(),
+            percentiles: smartness_settings.metrics_percentiles.clone().unwrap(),
             disabled: smartness_settings.no_metrics.unwrap(),
         }
     }
 
-    pub fn add_latency(&self, latency: f64) {
+    pub fn add_latency(&self, service_latency: Duration, response_latency: Duration) {
         if !self.disabled {
-            let old_count = self.count.fetch_add(1, ORDER_TYPE);
+            self.count.fetch_add(1, ORDER_TYPE);
+            let service_latency = self.latency_unit.record_value(service_latency);
+            let response_latency = self.latency_unit.record_value(response_latency);
+
+            self.recorder
+                .record_latency(service_latency, response_latency);
+        }
+    }
 
-            let mut metrics_store = self.metrics_store.lock().unwrap();
-            metrics_store.add_latency(latency, old_count);
+    pub fn add_error(
+        &self,
+        error_kind: MetricsErrorKind,
+        service_latency: Duration,
+        response_latency: Duration,
+    ) {
+        if !self.disabled {
+            self.failed_count.fetch_add(1, ORDER_TYPE);
+            self.error_counts[error_kind as usize].fetch_add(1, ORDER_TYPE);
+            let service_latency = self.latency_unit.record_value(service_latency);
+            let response_latency = self.latency_unit.record_value(response_latency);
+
+            self.recorder
+                .record_error(service_latency, response_latency);
+        }
+    }
+
+    pub fn add_dropped(&self) {
+        if !self.disabled {
+            self.dropped_count.fetch_add(1, ORDER_TYPE);
         }
     }
 
-    /// copy values from all vectors and generate snapshot...
+    pub fn add_delayed(&self) {
+        if !self.disabled {
+            self.delayed_count.fetch_add(1, ORDER_TYPE);
+        }
+    }
+
+    pub fn get_count(&self) -> u64 {
+        self.count.load(ORDER_TYPE)
+    }
+
+    pub fn get_failed_count(&self) -> u64 {
+        self.failed_count.load(ORDER_TYPE)
+    }
+
+    pub fn get_dropped_count(&self) -> u64 {
+        self.dropped_count.load(ORDER_TYPE)
+    }
+
+    pub fn get_delayed_count(&self) -> u64 {
+        self.delayed_count.load(ORDER_TYPE)
+    }
+
+    /// quantity of failed operations by MetricsErrorKind, in MetricsErrorKind::ALL order
+    pub fn get_error_counts(&self) -> Vec<u64> {
+        self.error_counts
+            .iter()
+            .map(|error_count| error_count.load(ORDER_TYPE))
+            .collect()
+    }
+
+    /// generate snapshot of service time...
     pub fn generate_snapshot(&self) -> MetricSnapshot {
-        let count = self.count.load(ORDER_TYPE);
+        self.snapshot(self.count.load(ORDER_TYPE), &self.metrics_store, false)
+    }
+
+    /// generate snapshot of response time, measured from intended start...
+    pub fn generate_response_snapshot(&self) -> MetricSnapshot {
+        self.snapshot(
+            self.count.load(ORDER_TYPE),
+            &self.response_metrics_store,
+            false,
+        )
+    }
 
-        let (latency_vec, drained_vec, windowed_vec) = self.get_latencies();
+    /// generate snapshot of service time of failed operations...
+    pub fn generate_failed_snapshot(&self) -> MetricSnapshot {
+        self.snapshot(
+            self.failed_count.load(ORDER_TYPE),
+            &self.failed_metrics_store,
+            false,
+        )
+    }
 
-        // latency_vec
-        let mut data_latency_vec = Data::new(latency_vec);
+    /// generate snapshot of response time of failed operations...
+    pub fn generate_failed_response_snapshot(&self) -> MetricSnapshot {
+        self.snapshot(
+            self.failed_count.load(ORDER_TYPE),
+            &self.failed_response_metrics_store,
+            false,
+        )
+    }
 
-        // drained_vec
-        let mut data_drained_vec = Data::new(drained_vec);
+    /// generate snapshot of service time and start a new interval,
+    /// only metrics rows take snapshots, so each row has the latencies since the previous one.
+    pub fn take_snapshot(&self) -> MetricSnapshot {
+        self.snapshot(self.count.load(ORDER_TYPE), &self.metrics_store, true)
+    }
 
-        // windowed_vec
-        let mut data_windowed_vec = Data::new(windowed_vec);
+    /// generate snapshot of response time and start a new interval...
+    pub fn take_response_snapshot(&self) -> MetricSnapshot {
+        self.snapshot(
+            self.count.load(ORDER_TYPE),
+            &self.response_metrics_store,
+            true,
+        )
+    }
 
-        // get min, max, mean, std_dev, median, 75perc, 95perc, 98perc, 99perc, 99_9perc
-        MetricSnapshot {
+    /// generate snapshot of service time of failed operations and start a new interval...
+    pub fn take_failed_snapshot(&self) -> MetricSnapshot {
+        self.snapshot(
+            self.failed_count.load(ORDER_TYPE),
+            &self.failed_metrics_store,
+            true,
+        )
+    }
+
+    /// generate snapshot of response time of failed operations and start a new interval...
+    pub fn take_failed_response_snapshot(&self) -> MetricSnapshot {
+        self.snapshot(
+            self.failed_count.load(ORDER_TYPE),
+            &self.failed_response_metrics_store,
+            true,
+        )
+    }
+
+    /// read stats from all histograms, it does not depend on the quantity of latencies...
+    /// interval is reset while the lock is held, so no latency is lost between two intervals.
+    fn snapshot(
+        &self,
+        count: u64,
+        metrics_store: &Mutex<MetricsStore>,
+        reset_interval: bool,
+    ) -> MetricSnapshot {
+        // latencies recorded since the last snapshot are moved into stores first...
+        self.recorder.merge_into(
+            &self.metrics_store,
+            &self.response_metrics_store,
+            &self.failed_metrics_store,
+            &self.failed_response_metrics_store,
+        );
+
+        let mut metrics_store = metrics_store.lock().unwrap();
+
+        let snapshot = MetricSnapshot {
             count,
-            min: if data_latency_vec.min().is_nan() {
-                0.0
-            } else {
-                data_latency_vec.min()
-            },
-            max: if data_latency_vec.max().is_nan() {
-                0.0
-            } else {
-                data_latency_vec.max()
-            },
-            mean: if data_latency_vec.mean().unwrap_or(0.0).is_nan() {
-                0.0
-            } else {
-                data_latency_vec.mean().unwrap_or(0.0)
-            },
-            std_dev: if data_latency_vec.std_dev().unwrap_or(0.0).is_nan() {
-                0.0
-            } else {
-                data_latency_vec.std_dev().unwrap_or(0.0)
-            },
-            median: if data_latency_vec.median().is_nan() {
-                0.0
-            } else {
-                data_latency_vec.median()
-            },
-            p_75th: if data_latency_vec.quantile(0.75).is_nan() {
-                0.0
-            } else {
-                data_latency_vec.quantile(0.75)
-            },
-            p_95th: if data_latency_vec.quantile(0.95).is_nan() {
-                0.0
-            } else {
-                data_latency_vec.quantile(0.95)
-            },
-            p_98th: if data_latency_vec.quantile(0.98).is_nan() {
-                0.0
-            } else {
-                data_latency_vec.quantile(0.98)
-            },
-            p_99th: if data_latency_vec.quantile(0.99).is_nan() {
-                0.0
-            } else {
-                data_latency_vec.quantile(0.99)
-            },
-            p_99_9th: if data_latency_vec.quantile(0.999).is_nan() {
-                0.0
-            } else {
-                data_latency_vec.quantile(0.999)
-            },
-            d_min: if data_drained_vec.min().is_nan() {
-                0.0
-            } else {
-                data_drained_vec.min()
-            },
-            d_max: if data_drained_vec.max().is_nan() {
-                0.0
-            } else {
-                data_drained_vec.max()
-            },
-            d_mean: if data_drained_vec.mean().unwrap_or(0.0).is_nan() {
-                0.0
-            } else {
-                data_drained_vec.mean().unwrap_or(0.0)
-            },
-            d_std_dev: if data_drained_vec.std_dev().unwrap_or(0.0).is_nan() {
-                0.0
-            } else {
-                data_drained_vec.std_dev().unwrap_or(0.0)
-            },
-            d_median: if data_drained_vec.median().is_nan() {
-                0.0
-            } else {
-                data_drained_vec.median()
-            },
-            d_p_75th: if data_drained_vec.quantile(0.75).is_nan() {
-                0.0
-            } else {
-                data_drained_vec.quantile(0.75)
-            },
-            d_p_95th: if data_drained_vec.quantile(0.95).is_nan() {
-                0.0
-            } else {
-                data_drained_vec.quantile(0.95)
-            },
-            d_p_98th: if data_drained_vec.quantile(0.98).is_nan() {
-                0.0
-            } else {
-                data_drained_vec.quantile(0.98)
-            },
-            d_p_99th: if data_drained_vec.quantile(0.99).is_nan() {
-                0.0
-            } else {
-                data_drained_vec.quantile(0.99)
-            },
-            d_p_99_9th: if data_drained_vec.quantile(0.999).is_nan() {
-                0.0
-            } else {
-                data_drained_vec.quantile(0.999)
-            },
-            w_min: if data_windowed_vec.min().is_nan() {
-                0.0
-            } else {
-                data_windowed_vec.min()
-            },
-            w_max: if data_windowed_vec.max().is_nan() {
-                0.0
-            } else {
-                data_windowed_vec.max()
-            },
-            w_mean: if data_windowed_vec.mean().unwrap_or(0.0).is_nan() {
-                0.0
-            } else {
-                data_windowed_vec.mean().unwrap_or(0.0)
-            },
-            w_std_dev: if data_windowed_vec.std_dev().unwrap_or(0.0).is_nan() {
-                0.0
-            } else {
-                data_windowed_vec.std_dev().unwrap_or(0.0)
-            },
-            w_median: if data_windowed_vec.median().is_nan() {
-                0.0
-            } else {
-                data_windowed_vec.median()
-            },
-            w_p_75th: if data_windowed_vec.quantile(0.75).is_nan() {
-                0.0
-            } else {
-                data_windowed_vec.quantile(0.75)
-            },
-            w_p_95th: if data_windowed_vec.quantile(0.95).is_nan() {
-                0.0
-            } else {
-                data_windowed_vec.quantile(0.95)
-            },
-            w_p_98th: if data_windowed_vec.quantile(0.98).is_nan() {
-                0.0
-            } else {
-                data_windowed_vec.quantile(0.98)
-            },
-            w_p_99th: if data_windowed_vec.quantile(0.99).is_nan() {
-                0.0
-            } else {
-                data_windowed_vec.quantile(0.99)
-            },
-            w_p_99_9th: if data_windowed_vec.quantile(0.999).is_nan() {
-                0.0
-            } else {
-                data_windowed_vec.quantile(0.999)
-            },
+            overall: LatencyStats::from_histogram(
+                metrics_store.get_latency_histogram(),
+                self.latency_unit,
+                &self.percentiles,
+            ),
+            overall_buckets: latency_buckets(
+                metrics_store.get_latency_histogram(),
+                self.latency_unit,
+            ),
+            drained: LatencyStats::from_histogram(
+                metrics_store.get_drained_histogram(),
+                self.latency_unit,
+                &self.percentiles,
+            ),
+            windowed: LatencyStats::from_histogram(
+                &metrics_store.get_windowed_histogram(),
+                self.latency_unit,
+                &self.percentiles,
+            ),
+            interval_count: metrics_store.get_interval_histogram().len(),
+            interval: LatencyStats::from_histogram(
+                metrics_store.get_interval_histogram(),
+                self.latency_unit,
+                &self.percentiles,
+            ),
+        };
+
+        if reset_interval {
+            metrics_store.reset_interval_histogram();
         }
+
+        snapshot
     }
+}
 
-    fn get_latencies(&self) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
-        let metrics_store = self.metrics_store.lock().unwrap();
-        (
-            metrics_store.get_latency_vec_clone(),
-            metrics_store.get_drained_vec_clone(),
-            metrics_store.get_windowed_vec_clone(),
-        )
+/// cumulative quantity of latencies by LATENCY_BUCKETS...
+/// recorded values are compared by their lowest equivalent value, so a latency equal to a bound
+/// is counted in its bucket.
+fn latency_buckets(histogram: &Histogram<u64>, latency_unit: LatencyUnit) -> Vec<u64> {
+    let mut buckets = vec![0; LATENCY_BUCKETS.len()];
+    for recorded in histogram.iter_recorded() {
+        let value = histogram.lowest_equivalent(recorded.value_iterated_to());
+        let seconds = latency_unit.seconds_value(latency_unit.output_value(value as f64));
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            buckets[bucket] += recorded.count_at_value();
+        }
+    }
+
+    for bucket in 1..buckets.len() {
+        buckets[bucket] += buckets[bucket - 1];
+    }
+    buckets
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::{
+        config::smarteness_settings::test_settings, metrics::metrics_store::new_histogram,
+    };
+
+    use super::*;
+
+    #[test]
+    fn latency_unit_records_and_outputs_values() {
+        let latency = Duration::from_micros(1_500);
+
+        assert_eq!(LatencyUnit::Ms.record_value(latency), 1_500);
+        assert_eq!(LatencyUnit::Us.record_value(latency), 1_500);
+        assert_eq!(LatencyUnit::Ns.record_value(latency), 1_500_000);
+
+        assert_eq!(LatencyUnit::Ms.output_value(1_500.0), 1.5);
+        assert_eq!(LatencyUnit::Us.output_value(1_500.0), 1_500.0);
+        assert_eq!(LatencyUnit::Ns.output_value(1_500_000.0), 1_500_000.0);
+
+        assert_eq!(LatencyUnit::Ms.seconds_value(1.5), 0.0015);
+        assert_eq!(LatencyUnit::Us.seconds_value(1_500.0), 0.0015);
+        assert_eq!(LatencyUnit::Ns.seconds_value(1_500_000.0), 0.0015);
+    }
+
+    // histograms keep 3 significant digits, so values are equivalent within 0.1%
+    fn assert_close(value: f64, expected: f64) {
+        assert!(
+            (value - expected).abs() <= expected * 1e-3,
+            "value {} expected {}",
+            value,
+            expected
+        );
+    }
+
+    #[test]
+    fn stats_are_converted_to_latency_unit() {
+        let mut histogram = new_histogram(3);
+        for micros in [1_000, 2_000, 3_000, 4_000] {
+            histogram.record(micros).unwrap();
+        }
+
+        let stats = LatencyStats::from_histogram(&histogram, LatencyUnit::Ms, &[75.0, 99.0]);
+
+        assert_close(stats.min, 1.0);
+        assert_close(stats.max, 4.0);
+        assert_close(stats.mean, 2.5);
+        assert_close(stats.median, 2.0);
+        assert_eq!(stats.percentiles.len(), 2);
+        assert_eq!(stats.percentiles[0].percentile, 75.0);
+        assert_close(stats.percentiles[0].value, 3.0);
+        assert_eq!(stats.percentiles[1].percentile, 99.0);
+        assert_close(stats.percentiles[1].value, 4.0);
+    }
+
+    #[test]
+    fn stats_of_empty_histogram_are_zero() {
+        let stats = LatencyStats::from_histogram(&new_histogram(3), LatencyUnit::Us, &[99.9]);
+
+        assert_eq!(stats.max, 0.0);
+        assert_eq!(stats.mean, 0.0);
+        assert_eq!(stats.percentiles.len(), 1);
+        assert_eq!(stats.percentiles[0].value, 0.0);
+    }
+
+    #[test]
+    fn latency_buckets_are_cumulative_and_include_their_bound() {
+        let mut histogram = new_histogram(3);
+        // 1ms is the bound of the second bucket, 3ms falls in the fourth one and 20s in none
+        for latency in [Duration::from_micros(200), Duration::from_millis(1)] {
+            histogram
+                .record(LatencyUnit::Ms.record_value(latency))
+                .unwrap();
+        }
+        histogram
+            .record_n(LatencyUnit::Ms.record_value(Duration::from_millis(3)), 2)
+            .unwrap();
+        histogram
+            .record(LatencyUnit::Ms.record_value(Duration::from_secs(20)))
+            .unwrap();
+
+        let buckets = latency_buckets(&histogram, LatencyUnit::Ms);
+        assert_eq!(buckets.len(), LATENCY_BUCKETS.len());
+        assert_eq!(buckets[..5], [1, 2, 2, 4, 4]);
+        assert_eq!(*buckets.last().unwrap(), 4);
+
+        // buckets do not depend on the unit of metrics files
+        let mut ns_histogram = new_histogram(3);
+        ns_histogram
+            .record(LatencyUnit::Ns.record_value(Duration::from_millis(3)))
+            .unwrap();
+        assert_eq!(
+            latency_buckets(&ns_histogram, LatencyUnit::Ns)[..4],
+            [0, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn take_snapshot_starts_a_new_interval() {
+        let metrics_manager = MetricsManager::new(&test_settings("interval-reset", json!({})));
+        for millis in [1, 2, 3] {
+            metrics_manager
+                .add_latency(Duration::from_millis(millis), Duration::from_millis(millis));
+        }
+
+        // generated snapshots read the interval without resetting it
+        assert_eq!(metrics_manager.generate_snapshot().interval_count, 3);
+        let snapshot = metrics_manager.take_snapshot();
+        assert_eq!(snapshot.interval_count, 3);
+        assert_close(snapshot.interval.max, 3.0);
+
+        metrics_manager.add_latency(Duration::from_millis(8), Duration::from_millis(9));
+        let snapshot = metrics_manager.take_snapshot();
+        assert_eq!(snapshot.count, 4);
+        assert_eq!(snapshot.interval_count, 1);
+        assert_close(snapshot.interval.min, 8.0);
+        assert_close(snapshot.overall.min, 1.0);
+
+        // an interval without latencies has empty stats
+        let snapshot = metrics_manager.take_snapshot();
+        assert_eq!(snapshot.interval_count, 0);
+        assert_eq!(snapshot.interval.max, 0.0);
+    }
+
+    #[test]
+    fn each_store_starts_its_own_interval() {
+        let metrics_manager = MetricsManager::new(&test_settings("interval-stores", json!({})));
+        metrics_manager.add_latency(Duration::from_millis(1), Duration::from_millis(2));
+
+        assert_eq!(metrics_manager.take_snapshot().interval_count, 1);
+        // response time interval is reset only by its own snapshot
+        assert_eq!(metrics_manager.take_response_snapshot().interval_count, 1);
+        assert_eq!(metrics_manager.take_response_snapshot().interval_count, 0);
+
+        metrics_manager.add_error(
+            MetricsErrorKind::Timeout,
+            Duration::from_millis(3),
+            Duration::from_millis(4),
+        );
+        assert_eq!(metrics_manager.take_failed_snapshot().interval_count, 1);
+        assert_eq!(metrics_manager.take_failed_snapshot().interval_count, 0);
+        assert_eq!(
+            metrics_manager
+                .take_failed_response_snapshot()
+                .interval_count,
+            1
+        );
+        // failed latencies do not reach the service time interval
+        assert_eq!(metrics_manager.take_snapshot().interval_count, 0);
     }
 }
@@ -0,0 +1,557 @@
+use std::{
+    io,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use scylla::observability::metrics::Metrics;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::smarteness_settings::{LatencyUnit, OperationKind},
+    csql::csql_operation::CsqlOperation,
+    metrics::{
+        metrics_error::MetricsErrorKind,
+        metrics_manager::{LATENCY_BUCKETS, MetricSnapshot},
+        metrics_rate::RateMeter,
+    },
+};
+
+// requests are small GET requests, so only their head is read...
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+// a connection that does not send its request in time is closed
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// serves MetricsManager counters and latencies of every operation and metrics of write
+/// and read sessions in Prometheus text format at /metrics, metrics are read on every scrape.
+pub struct PrometheusExporter {
+    pub operations: Vec<Arc<CsqlOperation>>,
+    pub current_phase: Arc<RwLock<String>>,
+    pub rate_meter: Arc<RateMeter>,
+    pub latency_unit: LatencyUnit,
+}
+
+impl PrometheusExporter {
+    /// accept connections until stop_token is cancelled...
+    pub async fn serve(self: Arc<Self>, listener: TcpListener, stop_token: CancellationToken) {
+        loop {
+            let stream = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(error) => {
+                        println!("Error when accept a Prometheus connection: {}", error);
+                        continue;
+                    }
+                },
+                _ = stop_token.cancelled() => break,
+            };
+
+            let exporter = Arc::clone(&self);
+            tokio::spawn(async move {
+                if let Err(error) = exporter.handle_connection(stream).await {
+                    println!("Error when serve Prometheus metrics: {}", error);
+                }
+            });
+        }
+    }
+
+    async fn handle_connection(&self, mut stream: TcpStream) -> io::Result<()> {
+        let mut request = Vec::with_capacity(1024);
+        let mut buffer = [0; 1024];
+
+        // read until the end of request head, body is ignored...
+        let read_head = async {
+            while !request.windows(4).any(|window| window == b"\r\n\r\n")
+                && request.len() < MAX_REQUEST_SIZE
+            {
+                let read = stream.read(&mut buffer).await?;
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..read]);
+            }
+            Ok::<(), io::Error>(())
+        };
+
+        time::timeout(REQUEST_TIMEOUT, read_head)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "request not received"))??;
+
+        let request = String::from_utf8_lossy(&request);
+        let mut request_line = request.lines().next().unwrap_or("").split_whitespace();
+        let method = request_line.next().unwrap_or("");
+        let path = request_line
+            .next()
+            .and_then(|target| target.split('?').next())
+            .unwrap_or("");
+
+        let (status, content_type, body) = match (method, path) {
+            ("GET", "/metrics") => ("200 OK", CONTENT_TYPE, self.render()),
+            ("GET", _) => ("404 Not Found", "text/plain", "not found\n".to_owned()),
+            _ => (
+                "405 Method Not Allowed",
+                "text/plain",
+                "method not allowed\n".to_owned(),
+            ),
+        };
+
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        );
+
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+
+    /// metrics in Prometheus text format...
+    /// names of operations and phases only have letters, digits, _ and -, so labels are not escaped.
+    fn render(&self) -> String {
+        let mut text = PrometheusText::default();
+
+        let phase = self.current_phase.read().unwrap().clone();
+        text.family("smartness_phase_info", "gauge", "phase that is running");
+        text.sample("smartness_phase_info", &[("phase", &phase)], 1.0);
+
+        text.family(
+            "smartness_target_rate",
+            "gauge",
+            "tasks per second targeted by the scheduler",
+        );
+        text.sample(
+            "smartness_target_rate",
+            &[],
+            self.rate_meter.get_target_rate(),
+        );
+
+        for (name, help, value) in [
+            (
+                "smartness_requests_started_total",
+                "requests started by the load generator",
+                self.rate_meter.get_started_count(),
+            ),
+            (
+                "smartness_requests_completed_total",
+                "requests completed successfully",
+                self.rate_meter.get_completed_count(),
+            ),
+            (
+                "smartness_requests_failed_total",
+                "requests completed with an error",
+                self.rate_meter.get_failed_count(),
+            ),
+        ] {
+            text.family(name, "counter", help);
+            text.sample(name, &[], value as f64);
+        }
+
+        let snapshots: Vec<[MetricSnapshot; 4]> = self
+            .operations
+            .iter()
+            .map(|operation| {
+                let metrics_manager = &operation.metrics_manager;
+                [
+                    metrics_manager.generate_snapshot(),
+                    metrics_manager.generate_response_snapshot(),
+                    metrics_manager.generate_failed_snapshot(),
+                    metrics_manager.generate_failed_response_snapshot(),
+                ]
+            })
+            .collect();
+
+        self.render_operation_counters(&mut text);
+
+        for (index, (name, help)) in [
+            (
+                "smartness_service_time_seconds",
+                "service time of successful operations, from request start",
+            ),
+            (
+                "smartness_response_time_seconds",
+                "response time of successful operations, from intended start",
+            ),
+            (
+                "smartness_failed_service_time_seconds",
+                "service time of failed operations",
+            ),
+            (
+                "smartness_failed_response_time_seconds",
+                "response time of failed operations, from intended start",
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            text.family(name, "histogram", help);
+            for (operation, operation_snapshots) in self.operations.iter().zip(&snapshots) {
+                self.render_histogram(
+                    &mut text,
+                    name,
+                    &operation.name,
+                    &operation_snapshots[index],
+                );
+            }
+        }
+
+        self.render_session_metrics(&mut text);
+
+        text.out
+    }
+
+    fn render_operation_counters(&self, text: &mut PrometheusText) {
+        type CounterGetter = fn(&CsqlOperation) -> u64;
+        let counters: [(&str, &str, CounterGetter); 4] = [
+            (
+                "smartness_operations_total",
+                "successful operations",
+                |operation| operation.metrics_manager.get_count(),
+            ),
+            (
+                "smartness_operations_failed_total",
+                "failed operations",
+                |operation| operation.metrics_manager.get_failed_count(),
+            ),
+            (
+                "smartness_operations_dropped_total",
+                "operations not sent because max_in_flight was reached",
+                |operation| operation.metrics_manager.get_dropped_count(),
+            ),
+            (
+                "smartness_operations_delayed_total",
+                "operations that waited because max_in_flight was reached",
+                |operation| operation.metrics_manager.get_delayed_count(),
+            ),
+        ];
+
+        for (name, help, counter) in counters {
+            text.family(name, "counter", help);
+            for operation in &self.operations {
+                text.sample(
+                    name,
+                    &[("operation", &operation.name)],
+                    counter(operation) as f64,
+                );
+            }
+        }
+
+        let name = "smartness_operation_errors_total";
+        text.family(name, "counter", "failed operations by error kind");
+        for operation in &self.operations {
+            let error_counts = operation.metrics_manager.get_error_counts();
+            for (error_kind, error_count) in MetricsErrorKind::ALL.iter().zip(error_counts) {
+                text.sample(
+                    name,
+                    &[("operation", &operation.name), ("error", error_kind.name())],
+                    error_count as f64,
+                );
+            }
+        }
+    }
+
+    /// buckets of all latencies recorded since the start of the run...
+    /// histograms are exposed instead of summaries, so quantiles can be aggregated across
+    /// operations and processes with histogram_quantile, percentiles are kept in metrics files.
+    fn render_histogram(
+        &self,
+        text: &mut PrometheusText,
+        name: &str,
+        operation_name: &str,
+        snapshot: &MetricSnapshot,
+    ) {
+        let bucket_name = format!("{}_bucket", name);
+        for (bound, bucket_count) in LATENCY_BUCKETS.iter().zip(&snapshot.overall_buckets) {
+            text.sample(
+                &bucket_name,
+                &[("operation", operation_name), ("le", &bound.to_string())],
+                *bucket_count as f64,
+            );
+        }
+        text.sample(
+            &bucket_name,
+            &[("operation", operation_name), ("le", "+Inf")],
+            snapshot.count as f64,
+        );
+
+        let labels = [("operation", operation_name)];
+        text.sample(
+            &format!("{}_sum", name),
+            &labels,
+            self.latency_unit.seconds_value(snapshot.overall.mean) * snapshot.count as f64,
+        );
+        text.sample(&format!("{}_count", name), &labels, snapshot.count as f64);
+    }
+
+    /// metrics of write and read ScyllaDB sessions...
+    /// all operations of a kind share its session, so each session is written once.
+    fn render_session_metrics(&self, text: &mut PrometheusText) {
+        let metrics: Vec<_> = [
+            (OperationKind::Write, "write"),
+            (OperationKind::Read, "read"),
+        ]
+        .into_iter()
+        .filter_map(|(kind, session_name)| {
+            self.operations
+                .iter()
+                .find(|operation| operation.kind == kind)
+                .map(|operation| (session_name, operation.session.get_metrics()))
+        })
+        .collect();
+
+        type SessionGetter = fn(&Metrics) -> f64;
+        let families: [(&str, &str, &str, SessionGetter); 13] = [
+            (
+                "smartness_session_queries_total",
+                "counter",
+                "queries sent by the session",
+                |metrics| metrics.get_queries_num() as f64,
+            ),
+            (
+                "smartness_session_queries_iter_total",
+                "counter",
+                "paged queries sent by the session",
+                |metrics| metrics.get_queries_iter_num() as f64,
+            ),
+            (
+                "smartness_session_errors_total",
+                "counter",
+                "errors of queries sent by the session",
+                |metrics| metrics.get_errors_num() as f64,
+            ),
+            (
+                "smartness_session_errors_iter_total",
+                "counter",
+                "errors of paged queries sent by the session",
+                |metrics| metrics.get_errors_iter_num() as f64,
+            ),
+            (
+                "smartness_session_latency_average_seconds",
+                "gauge",
+                "average latency measured by the session",
+                |metrics| metrics.get_latency_avg_ms().unwrap_or(0) as f64 / 1_000.0,
+            ),
+            (
+                "smartness_session_latency_p999_seconds",
+                "gauge",
+                "99.9th percentile of latency measured by the session",
+                |metrics| metrics.get_latency_percentile_ms(99.9).unwrap_or(0) as f64 / 1_000.0,
+            ),
+            (
+                "smartness_session_mean_rate",
+                "gauge",
+                "mean rate of queries per second",
+                |metrics| metrics.get_mean_rate(),
+            ),
+            (
+                "smartness_session_one_minute_rate",
+                "gauge",
+                "one minute rate of queries per second",
+                |metrics| metrics.get_one_minute_rate(),
+            ),
+            (
+                "smartness_session_five_minute_rate",
+                "gauge",
+                "five minute rate of queries per second",
+                |metrics| metrics.get_five_minute_rate(),
+            ),
+            (
+                "smartness_session_fifteen_minute_rate",
+                "gauge",
+                "fifteen minute rate of queries per second",
+                |metrics| metrics.get_fifteen_minute_rate(),
+            ),
+            (
+                "smartness_session_connections",
+                "gauge",
+                "connections opened by the session",
+                |metrics| metrics.get_total_connections() as f64,
+            ),
+            (
+                "smartness_session_connection_timeouts_total",
+                "counter",
+                "connections of the session that timed out",
+                |metrics| metrics.get_connection_timeouts() as f64,
+            ),
+            (
+                "smartness_session_request_timeouts_total",
+                "counter",
+                "requests of the session that timed out",
+                |metrics| metrics.get_request_timeouts() as f64,
+            ),
+        ];
+
+        for (name, metric_type, help, getter) in families {
+            text.family(name, metric_type, help);
+            for (session_name, session_metrics) in &metrics {
+                text.sample(name, &[("session", session_name)], getter(session_metrics));
+            }
+        }
+    }
+}
+
+// text exposition format, a family header followed by its samples
+#[derive(Default)]
+struct PrometheusText {
+    out: String,
+}
+
+impl PrometheusText {
+    fn family(&mut self, name: &str, metric_type: &str, help: &str) {
+        self.out.push_str(&format!(
+            "# HELP {} {}\n# TYPE {} {}\n",
+            name, help, name, metric_type
+        ));
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, value))
+                .collect();
+            self.out.push_str(&format!("{{{}}}", labels.join(",")));
+        }
+
+        // Prometheus writes infinity as +Inf and -Inf
+        let value = if value == f64::INFINITY {
+            "+Inf".to_owned()
+        } else if value == f64::NEG_INFINITY {
+            "-Inf".to_owned()
+        } else {
+            value.to_string()
+        };
+        self.out.push_str(&format!(" {}\n", value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::metrics_manager::{LatencyStats, PercentileValue};
+
+    use super::*;
+
+    fn exporter() -> PrometheusExporter {
+        PrometheusExporter {
+            operations: Vec::new(),
+            current_phase: Arc::new(RwLock::new("ramp".to_owned())),
+            rate_meter: Arc::new(RateMeter::default()),
+            latency_unit: LatencyUnit::Ms,
+        }
+    }
+
+    fn stats(median: f64, mean: f64, percentiles: &[(f64, f64)]) -> LatencyStats {
+        LatencyStats {
+            min: 0.0,
+            max: 0.0,
+            mean,
+            std_dev: 0.0,
+            median,
+            percentiles: percentiles
+                .iter()
+                .map(|(percentile, value)| PercentileValue {
+                    percentile: *percentile,
+                    value: *value,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn text_writes_families_and_samples() {
+        let mut text = PrometheusText::default();
+        text.family("smartness_test", "gauge", "a test metric");
+        text.sample("smartness_test", &[], 1.5);
+        text.sample(
+            "smartness_test",
+            &[("operation", "w"), ("error", "timeout")],
+            f64::INFINITY,
+        );
+        text.sample("smartness_test", &[("operation", "r")], f64::NEG_INFINITY);
+
+        assert_eq!(
+            text.out,
+            "# HELP smartness_test a test metric\n\
+             # TYPE smartness_test gauge\n\
+             smartness_test 1.5\n\
+             smartness_test{operation=\"w\",error=\"timeout\"} +Inf\n\
+             smartness_test{operation=\"r\"} -Inf\n"
+        );
+    }
+
+    #[test]
+    fn render_writes_phase_rate_and_request_counters() {
+        let exporter = exporter();
+        exporter.rate_meter.set_target_rate(250.0);
+        for _ in 0..3 {
+            exporter.rate_meter.add_started();
+        }
+        exporter.rate_meter.add_completed();
+        exporter.rate_meter.add_failed();
+
+        let text = exporter.render();
+
+        for line in [
+            "smartness_phase_info{phase=\"ramp\"} 1",
+            "smartness_target_rate 250",
+            "smartness_requests_started_total 3",
+            "smartness_requests_completed_total 1",
+            "smartness_requests_failed_total 1",
+            "# TYPE smartness_service_time_seconds histogram",
+        ] {
+            assert!(text.lines().any(|text_line| text_line == line), "{}", line);
+        }
+    }
+
+    #[test]
+    fn render_histogram_writes_buckets_sum_and_count_in_seconds() {
+        let exporter = exporter();
+        let mut overall_buckets = vec![0; LATENCY_BUCKETS.len()];
+        // one latency of 2ms and three of 8ms
+        overall_buckets[3..].fill(1);
+        overall_buckets[4..].fill(4);
+        let snapshot = MetricSnapshot {
+            count: 4,
+            overall: stats(8.0, 6.5, &[(99.9, 8.0)]),
+            overall_buckets,
+            drained: stats(0.0, 0.0, &[]),
+            windowed: stats(0.0, 0.0, &[]),
+            interval_count: 0,
+            interval: stats(0.0, 0.0, &[]),
+        };
+
+        let mut text = PrometheusText::default();
+        exporter.render_histogram(&mut text, "smartness_service_time_seconds", "w", &snapshot);
+
+        let lines: Vec<&str> = text.out.lines().collect();
+        assert_eq!(lines.len(), LATENCY_BUCKETS.len() + 3);
+        assert_eq!(
+            lines[..5],
+            [
+                "smartness_service_time_seconds_bucket{operation=\"w\",le=\"0.0005\"} 0",
+                "smartness_service_time_seconds_bucket{operation=\"w\",le=\"0.001\"} 0",
+                "smartness_service_time_seconds_bucket{operation=\"w\",le=\"0.0025\"} 0",
+                "smartness_service_time_seconds_bucket{operation=\"w\",le=\"0.005\"} 1",
+                "smartness_service_time_seconds_bucket{operation=\"w\",le=\"0.01\"} 4",
+            ]
+        );
+        assert_eq!(
+            lines[lines.len() - 3..],
+            [
+                "smartness_service_time_seconds_bucket{operation=\"w\",le=\"+Inf\"} 4",
+                "smartness_service_time_seconds_sum{operation=\"w\"} 0.026",
+                "smartness_service_time_seconds_count{operation=\"w\"} 4",
+            ]
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const ORDER_TYPE: Ordering = Ordering::Relaxed;
+
+/// target rate of the scheduler and quantity of started, completed and failed tasks,
+/// read by metrics runtime on every metrics row to report the achieved rate and by the final summary.
+#[derive(Default)]
+pub struct RateMeter {
+    /// bits of f64 target rate in tasks per second, 0 bits are 0.0, so there is no target
+    target_rate: AtomicU64,
+    started_count: AtomicU64,
+    completed_count: AtomicU64,
+    failed_count: AtomicU64,
+}
+
+impl RateMeter {
+    pub fn set_target_rate(&self, target_rate: f64) {
+        self.target_rate.store(target_rate.to_bits(), ORDER_TYPE);
+    }
+
+    pub fn add_started(&self) {
+        self.started_count.fetch_add(1, ORDER_TYPE);
+    }
+
+    pub fn add_completed(&self) {
+        self.completed_count.fetch_add(1, ORDER_TYPE);
+    }
+
+    pub fn add_failed(&self) {
+        self.failed_count.fetch_add(1, ORDER_TYPE);
+    }
+
+    pub fn get_target_rate(&self) -> f64 {
+        f64::from_bits(self.target_rate.load(ORDER_TYPE))
+    }
+
+    pub fn get_started_count(&self) -> u64 {
+        self.started_count.load(ORDER_TYPE)
+    }
+
+    pub fn get_completed_count(&self) -> u64 {
+        self.completed_count.load(ORDER_TYPE)
+    }
+
+    pub fn get_failed_count(&self) -> u64 {
+        self.failed_count.load(ORDER_TYPE)
+    }
+
+    /// started tasks that are not completed nor failed yet
+    pub fn get_in_flight_count(&self) -> u64 {
+        let finished = self.get_completed_count() + self.get_failed_count();
+        self.get_started_count().saturating_sub(finished)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_rate_keeps_its_fraction() {
+        let rate_meter = RateMeter::default();
+        assert_eq!(rate_meter.get_target_rate(), 0.0);
+
+        rate_meter.set_target_rate(1234.5);
+        assert_eq!(rate_meter.get_target_rate(), 1234.5);
+    }
+
+    #[test]
+    fn in_flight_count_excludes_completed_and_failed_requests() {
+        let rate_meter = RateMeter::default();
+        for _ in 0..5 {
+            rate_meter.add_started();
+        }
+        rate_meter.add_completed();
+        rate_meter.add_failed();
+        assert_eq!(rate_meter.get_in_flight_count(), 3);
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+use hdrhistogram::Histogram;
+
+use crate::{
+    config::smarteness_settings::SmartnessSettings,
+    metrics::metrics_store::{MetricsStore, new_histogram},
+};
+
+// next shard given to a thread that records its first latency
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // every thread keeps recording into the same shard...
+    static SHARD_INDEX: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed);
+}
+
+/// latencies recorded by the threads of a shard since the last merge
+struct LatencyShard {
+    service: Histogram<u64>,
+    response: Histogram<u64>,
+    failed: Histogram<u64>,
+    failed_response: Histogram<u64>,
+}
+
+/// records latencies into one shard per thread, so workers do not wait for each other,
+/// and merges all shards into metrics stores when metrics are read.
+pub struct ShardedRecorder {
+    shards: Vec<Mutex<LatencyShard>>,
+}
+
+impl ShardedRecorder {
+    pub fn new(smartness_settings: &SmartnessSettings) -> Self {
+        let precision = smartness_settings.metrics_histogram_precision.unwrap();
+
+        // one shard per worker thread and one more to the thread running the phases...
+        let workers = smartness_settings.workers.unwrap_or_else(|| {
+            thread::available_parallelism()
+                .map(|parallelism| parallelism.get())
+                .unwrap_or(1)
+        });
+
+        ShardedRecorder {
+            shards: (0..workers + 1)
+                .map(|_| {
+                    Mutex::new(LatencyShard {
+                        service: new_histogram(precision),
+                        response: new_histogram(precision),
+                        failed: new_histogram(precision),
+                        failed_response: new_histogram(precision),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    pub fn record_latency(&self, service_latency: u64, response_latency: u64) {
+        let mut shard = self.shard().lock().unwrap();
+        // shard histograms are auto resizable, saturating_record would clamp latencies
+        // above their initial range instead of growing them...
+        let _ = shard.service.record(service_latency);
+        let _ = shard.response.record(response_latency);
+    }
+
+    pub fn record_error(&self, service_latency: u64, response_latency: u64) {
+        let mut shard = self.shard().lock().unwrap();
+        let _ = shard.failed.record(service_latency);
+        let _ = shard.failed_response.record(response_latency);
+    }
+
+    /// move latencies of all shards into stores, shards are reset after the merge...
+    /// a shard is locked only while it is merged, so workers of other shards keep recording.
+    pub fn merge_into(
+        &self,
+        metrics_store: &Mutex<MetricsStore>,
+        response_metrics_store: &Mutex<MetricsStore>,
+        failed_metrics_store: &Mutex<MetricsStore>,
+        failed_response_metrics_store: &Mutex<MetricsStore>,
+    ) {
+        for shard in &self.shards {
+            let mut guard = shard.lock().unwrap();
+            let shard = &mut *guard;
+
+            for (histogram, store) in [
+                (&mut shard.service, metrics_store),
+                (&mut shard.response, response_metrics_store),
+                (&mut shard.failed, failed_metrics_store),
+                (&mut shard.failed_response, failed_response_metrics_store),
+            ] {
+                if !histogram.is_empty() {
+                    store.lock().unwrap().add_histogram(histogram);
+                    histogram.reset();
+                }
+            }
+        }
+    }
+
+    fn shard(&self) -> &Mutex<LatencyShard> {
+        let index = SHARD_INDEX.with(|index| *index);
+        &self.shards[index % self.shards.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::config::smarteness_settings::test_settings;
+
+    use super::*;
+
+    struct Stores {
+        service: Mutex<MetricsStore>,
+        response: Mutex<MetricsStore>,
+        failed: Mutex<MetricsStore>,
+        failed_response: Mutex<MetricsStore>,
+    }
+
+    impl Stores {
+        fn new(smartness_settings: &SmartnessSettings) -> Self {
+            Stores {
+                service: Mutex::new(MetricsStore::new(smartness_settings)),
+                response: Mutex::new(MetricsStore::new(smartness_settings)),
+                failed: Mutex::new(MetricsStore::new(smartness_settings)),
+                failed_response: Mutex::new(MetricsStore::new(smartness_settings)),
+            }
+        }
+
+        fn merge(&self, recorder: &ShardedRecorder) {
+            recorder.merge_into(
+                &self.service,
+                &self.response,
+                &self.failed,
+                &self.failed_response,
+            );
+        }
+
+        // recorded values of the total histogram of a store, as (value, count) pairs
+        fn values(store: &Mutex<MetricsStore>) -> Vec<(u64, u64)> {
+            let store = store.lock().unwrap();
+            let histogram = store.get_latency_histogram();
+            histogram
+                .iter_recorded()
+                .map(|value| {
+                    (
+                        histogram.lowest_equivalent(value.value_iterated_to()),
+                        value.count_at_value(),
+                    )
+                })
+                .collect()
+        }
+    }
+
+    fn recorder_settings(test_name: &str) -> SmartnessSettings {
+        test_settings(test_name, json!({ "workers": 3 }))
+    }
+
+    fn shards_are_empty(recorder: &ShardedRecorder) -> bool {
+        recorder.shards.iter().all(|shard| {
+            let shard = shard.lock().unwrap();
+            shard.service.is_empty()
+                && shard.response.is_empty()
+                && shard.failed.is_empty()
+                && shard.failed_response.is_empty()
+        })
+    }
+
+    #[test]
+    fn merge_moves_latencies_into_their_stores() {
+        let smartness_settings = recorder_settings("recorder_merge");
+        let recorder = ShardedRecorder::new(&smartness_settings);
+        let stores = Stores::new(&smartness_settings);
+
+        recorder.record_latency(1_000, 1_500);
+        recorder.record_latency(2_000, 2_500);
+        recorder.record_error(30_000, 36_000);
+        stores.merge(&recorder);
+
+        assert_eq!(
+            Stores::values(&stores.service),
+            vec![(1_000, 1), (2_000, 1)]
+        );
+        assert_eq!(
+            Stores::values(&stores.response),
+            vec![(1_500, 1), (2_500, 1)]
+        );
+        assert_eq!(Stores::values(&stores.failed), vec![(30_000, 1)]);
+        assert_eq!(Stores::values(&stores.failed_response), vec![(36_000, 1)]);
+    }
+
+    #[test]
+    fn shards_are_reset_after_a_merge() {
+        let smartness_settings = recorder_settings("recorder_reset");
+        let recorder = ShardedRecorder::new(&smartness_settings);
+        let stores = Stores::new(&smartness_settings);
+
+        recorder.record_latency(1_000, 1_500);
+        recorder.record_error(3_000, 3_500);
+        stores.merge(&recorder);
+        assert!(shards_are_empty(&recorder));
+
+        // a second merge without new latencies adds nothing...
+        stores.merge(&recorder);
+        assert_eq!(Stores::values(&stores.service), vec![(1_000, 1)]);
+        assert_eq!(Stores::values(&stores.failed_response), vec![(3_500, 1)]);
+
+        // and latencies recorded after a merge are added once to the previous ones
+        recorder.record_latency(1_000, 1_500);
+        stores.merge(&recorder);
+        assert_eq!(Stores::values(&stores.service), vec![(1_000, 2)]);
+        assert_eq!(Stores::values(&stores.response), vec![(1_500, 2)]);
+        assert_eq!(Stores::values(&stores.failed), vec![(3_000, 1)]);
+        assert_eq!(Stores::values(&stores.failed_response), vec![(3_500, 1)]);
+    }
+
+    #[test]
+    fn merge_keeps_latencies_of_every_shard() {
+        let smartness_settings = recorder_settings("recorder_threads");
+        let recorder = ShardedRecorder::new(&smartness_settings);
+        let stores = Stores::new(&smartness_settings);
+
+        // more threads than shards, so some threads share a shard
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..100 {
+                        recorder.record_latency(1_000, 2_000);
+                        recorder.record_error(3_000, 4_000);
+                    }
+                });
+            }
+        });
+        stores.merge(&recorder);
+
+        assert!(shards_are_empty(&recorder));
+        assert_eq!(Stores::values(&stores.service), vec![(1_000, 800)]);
+        assert_eq!(Stores::values(&stores.response), vec![(2_000, 800)]);
+        assert_eq!(Stores::values(&stores.failed), vec![(3_000, 800)]);
+        assert_eq!(Stores::values(&stores.failed_response), vec![(4_000, 800)]);
+    }
+
+    #[test]
+    fn latencies_above_the_initial_range_are_not_clamped() {
+        let smartness_settings = recorder_settings("recorder_range");
+        let recorder = ShardedRecorder::new(&smartness_settings);
+        let stores = Stores::new(&smartness_settings);
+
+        recorder.record_latency(10_000_000, 10_000_000);
+        stores.merge(&recorder);
+
+        let store = stores.service.lock().unwrap();
+        let histogram = store.get_latency_histogram();
+        assert!(histogram.equivalent(histogram.max(), 10_000_000));
+    }
+}
@@ -0,0 +1,365 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::BufWriter,
+    path::Path,
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    config::smarteness_settings::{LatencyUnit, OperationKind, SmartnessSettings},
+    csql::csql_operation::CsqlOperation,
+    error::SmartnessError,
+    metrics::{
+        metrics_error::MetricsErrorKind, metrics_manager::LatencyStats, metrics_rate::RateMeter,
+    },
+};
+
+/// end of run report, printed to the terminal and saved as JSON next to metrics files
+#[derive(Serialize)]
+pub struct RunReport {
+    /// RFC 3339 timestamps
+    pub started_at: String,
+    pub finished_at: String,
+    pub duration_secs: f64,
+    pub started: u64,
+    pub completed: u64,
+    pub failed: u64,
+    pub abandoned: u64,
+    /// completed requests per second
+    pub throughput: f64,
+    pub latency_unit: LatencyUnit,
+    /// false when run with --no-metrics, operations are not reported then
+    pub metrics_enabled: bool,
+    /// counts and latencies of operations, None if metrics are disabled
+    pub operations: Option<Vec<OperationReport>>,
+    /// effective settings, password is hidden
+    pub settings: Value,
+}
+
+#[derive(Serialize)]
+pub struct OperationReport {
+    pub name: String,
+    pub kind: OperationKind,
+    pub weight: u32,
+    pub count: u64,
+    pub failed_count: u64,
+    pub dropped_count: u64,
+    pub delayed_count: u64,
+    /// successful operations per second
+    pub throughput: f64,
+    pub errors: BTreeMap<&'static str, u64>,
+    pub service_time: LatencyStats,
+    pub response_time: LatencyStats,
+    pub failed_service_time: LatencyStats,
+    pub failed_response_time: LatencyStats,
+}
+
+impl RunReport {
+    pub fn new(
+        smartness_settings: &SmartnessSettings,
+        operations: &[Arc<CsqlOperation>],
+        rate_meter: &RateMeter,
+        started_at: DateTime<Utc>,
+        finished_at: DateTime<Utc>,
+    ) -> Self {
+        let duration_secs = (finished_at - started_at)
+            .as_seconds_f64()
+            .max(f64::EPSILON);
+
+        let started = rate_meter.get_started_count();
+        let completed = rate_meter.get_completed_count();
+        let failed = rate_meter.get_failed_count();
+
+        // operations record nothing with --no-metrics, so their stats would be all zero...
+        let metrics_enabled = !smartness_settings.no_metrics.unwrap();
+        let operations = metrics_enabled.then(|| {
+            operations
+                .iter()
+                .map(|operation| {
+                    let metrics_manager = &operation.metrics_manager;
+                    let snapshot = metrics_manager.generate_snapshot();
+                    let response_snapshot = metrics_manager.generate_response_snapshot();
+                    let failed_snapshot = metrics_manager.generate_failed_snapshot();
+                    let failed_response_snapshot =
+                        metrics_manager.generate_failed_response_snapshot();
+
+                    OperationReport {
+                        name: operation.name.clone(),
+                        kind: operation.kind,
+                        weight: operation.weight,
+                        count: snapshot.count,
+                        failed_count: failed_snapshot.count,
+                        dropped_count: metrics_manager.get_dropped_count(),
+                        delayed_count: metrics_manager.get_delayed_count(),
+                        throughput: snapshot.count as f64 / duration_secs,
+                        errors: MetricsErrorKind::ALL
+                            .iter()
+                            .map(MetricsErrorKind::name)
+                            .zip(metrics_manager.get_error_counts())
+                            .collect(),
+                        service_time: snapshot.overall,
+                        response_time: response_snapshot.overall,
+                        failed_service_time: failed_snapshot.overall,
+                        failed_response_time: failed_response_snapshot.overall,
+                    }
+                })
+                .collect()
+        });
+
+        // settings are saved to describe the experiment, but not the password...
+        let mut settings = serde_json::to_value(smartness_settings).unwrap_or(Value::Null);
+        if let Some(password) = settings.get_mut("cassandra_password")
+            && !password.is_null()
+        {
+            *password = Value::String("***".to_owned());
+        }
+
+        RunReport {
+            started_at: started_at.to_rfc3339(),
+            finished_at: finished_at.to_rfc3339(),
+            duration_secs,
+            started,
+            completed,
+            failed,
+            abandoned: started.saturating_sub(completed + failed),
+            throughput: completed as f64 / duration_secs,
+            latency_unit: smartness_settings.metrics_latency_unit.unwrap(),
+            metrics_enabled,
+            operations,
+            settings,
+        }
+    }
+
+    pub fn print(&self) {
+        print!("{}", self.render());
+    }
+
+    /// text printed to the terminal, one line per run, operation and latency stats
+    fn render(&self) -> String {
+        let mut text = String::new();
+        text.push_str("Run summary\n");
+        text.push_str(&format!(
+            "Duration (seconds): {:.3} | Completed: {} | Failed: {} | Abandoned: {} | Throughput (ops/sec): {:.3}\n",
+            self.duration_secs, self.completed, self.failed, self.abandoned, self.throughput
+        ));
+
+        let Some(operations) = self.operations.as_ref() else {
+            text.push_str("Operations are not reported, metrics are disabled.\n");
+            return text;
+        };
+
+        for operation in operations {
+            text.push_str(&format!(
+                "Operation {} ({:?}) | Count: {} | Failed: {} | Dropped: {} | Delayed: {} | Throughput (ops/sec): {:.3}\n",
+                operation.name,
+                operation.kind,
+                operation.count,
+                operation.failed_count,
+                operation.dropped_count,
+                operation.delayed_count,
+                operation.throughput
+            ));
+
+            let errors: Vec<String> = operation
+                .errors
+                .iter()
+                .filter(|(_, count)| **count > 0)
+                .map(|(kind, count)| format!("{}: {}", kind, count))
+                .collect();
+            if !errors.is_empty() {
+                text.push_str(&format!("    Errors | {}\n", errors.join(" | ")));
+            }
+
+            render_latency(
+                &mut text,
+                "Service time",
+                &operation.service_time,
+                self.latency_unit,
+            );
+            render_latency(
+                &mut text,
+                "Response time",
+                &operation.response_time,
+                self.latency_unit,
+            );
+            if operation.failed_count > 0 {
+                render_latency(
+                    &mut text,
+                    "Failed service time",
+                    &operation.failed_service_time,
+                    self.latency_unit,
+                );
+                render_latency(
+                    &mut text,
+                    "Failed response time",
+                    &operation.failed_response_time,
+                    self.latency_unit,
+                );
+            }
+        }
+
+        text
+    }
+
+    /// save report as {metrics_dir}/{file_name}_summary.json
+    pub fn save(&self, metrics_dir: &str, file_name: &str) -> Result<(), SmartnessError> {
+        let report_file_name = format!("{}/{}_summary.json", metrics_dir, file_name);
+        let report_path = Path::new(&report_file_name);
+
+        if let Some(parent) = report_path.parent() {
+            fs::create_dir_all(parent).map_err(SmartnessError::MetricsParentPathCreateError)?;
+        }
+
+        let report_file =
+            File::create(report_path).map_err(SmartnessError::MetricsReportCreateError)?;
+        serde_json::to_writer_pretty(BufWriter::new(report_file), self)
+            .map_err(SmartnessError::MetricsReportWriteError)?;
+
+        println!("Run summary file: {}", report_file_name);
+        Ok(())
+    }
+}
+
+fn render_latency(text: &mut String, title: &str, stats: &LatencyStats, latency_unit: LatencyUnit) {
+    let percentiles: String = stats
+        .percentiles
+        .iter()
+        .map(|percentile| format!(" | {}th: {:.3}", percentile.percentile, percentile.value))
+        .collect();
+
+    text.push_str(&format!(
+        "    {} ({:?}) | min: {:.3} | max: {:.3} | mean: {:.3} | std_dev: {:.3} | median: {:.3}{}\n",
+        title,
+        latency_unit,
+        stats.min,
+        stats.max,
+        stats.mean,
+        stats.std_dev,
+        stats.median,
+        percentiles
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        config::smarteness_settings::test_settings, metrics::metrics_manager::PercentileValue,
+    };
+
+    fn report(smartness_settings: &SmartnessSettings) -> RunReport {
+        let rate_meter = RateMeter::default();
+        for _ in 0..10 {
+            rate_meter.add_started();
+        }
+        for _ in 0..6 {
+            rate_meter.add_completed();
+        }
+        rate_meter.add_failed();
+
+        let started_at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let finished_at = DateTime::from_timestamp(1_700_000_002, 0).unwrap();
+        RunReport::new(
+            smartness_settings,
+            &[],
+            &rate_meter,
+            started_at,
+            finished_at,
+        )
+    }
+
+    fn latency_stats(min: f64, max: f64) -> LatencyStats {
+        LatencyStats {
+            min,
+            max,
+            mean: (min + max) / 2.0,
+            std_dev: 1.0,
+            median: (min + max) / 2.0,
+            percentiles: vec![PercentileValue {
+                percentile: 99.9,
+                value: max,
+            }],
+        }
+    }
+
+    #[test]
+    fn new_counts_requests_and_hides_password() {
+        let smartness_settings = test_settings(
+            "report-password",
+            json!({ "cassandra_username": "user", "cassandra_password": "secret" }),
+        );
+        let report = report(&smartness_settings);
+
+        assert_eq!(report.duration_secs, 2.0);
+        assert_eq!(report.abandoned, 3);
+        assert_eq!(report.throughput, 3.0);
+        assert!(report.metrics_enabled);
+        assert_eq!(report.operations.as_ref().map(Vec::len), Some(0));
+
+        assert_eq!(report.settings["cassandra_username"], json!("user"));
+        assert_eq!(report.settings["cassandra_password"], json!("***"));
+        assert!(!serde_json::to_string(&report).unwrap().contains("secret"));
+    }
+
+    #[test]
+    fn new_keeps_a_missing_password_null() {
+        let report = report(&test_settings("report-no-password", json!({})));
+        assert_eq!(report.settings["cassandra_password"], Value::Null);
+    }
+
+    #[test]
+    fn render_reports_no_operations_without_metrics() {
+        let mut smartness_settings = test_settings("report-no-metrics", json!({}));
+        smartness_settings.no_metrics = Some(true);
+        let report = report(&smartness_settings);
+
+        assert!(report.operations.is_none());
+        assert_eq!(
+            report.render(),
+            "Run summary\n\
+             Duration (seconds): 2.000 | Completed: 6 | Failed: 1 | Abandoned: 3 | Throughput (ops/sec): 3.000\n\
+             Operations are not reported, metrics are disabled.\n"
+        );
+    }
+
+    #[test]
+    fn render_reports_operations_errors_and_failed_latencies() {
+        let mut report = report(&test_settings("report-render", json!({})));
+        report.operations = Some(vec![OperationReport {
+            name: "w".to_owned(),
+            kind: OperationKind::Write,
+            weight: 1,
+            count: 6,
+            failed_count: 1,
+            dropped_count: 2,
+            delayed_count: 0,
+            throughput: 3.0,
+            errors: BTreeMap::from([("timeout", 1), ("other", 0)]),
+            service_time: latency_stats(1.0, 3.0),
+            response_time: latency_stats(2.0, 4.0),
+            failed_service_time: latency_stats(5.0, 5.0),
+            failed_response_time: latency_stats(6.0, 6.0),
+        }]);
+
+        let text = report.render();
+        let lines: Vec<&str> = text.lines().skip(2).collect();
+        assert_eq!(
+            lines,
+            [
+                "Operation w (Write) | Count: 6 | Failed: 1 | Dropped: 2 | Delayed: 0 | Throughput (ops/sec): 3.000",
+                "    Errors | timeout: 1",
+                "    Service time (Ms) | min: 1.000 | max: 3.000 | mean: 2.000 | std_dev: 1.000 | median: 2.000 | 99.9th: 3.000",
+                "    Response time (Ms) | min: 2.000 | max: 4.000 | mean: 3.000 | std_dev: 1.000 | median: 3.000 | 99.9th: 4.000",
+                "    Failed service time (Ms) | min: 5.000 | max: 5.000 | mean: 5.000 | std_dev: 1.000 | median: 5.000 | 99.9th: 5.000",
+                "    Failed response time (Ms) | min: 6.000 | max: 6.000 | mean: 6.000 | std_dev: 1.000 | median: 6.000 | 99.9th: 6.000",
+            ]
+        );
+    }
+}
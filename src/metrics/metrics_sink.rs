@@ -0,0 +1,80 @@
+use std::{fs, path::Path};
+
+use crate::{
+    config::smarteness_settings::MetricsSinkKind,
+    error::SmartnessError,
+    metrics::{
+        metrics_sink_csv::CsvSink, metrics_sink_jsonl::JsonLinesSink,
+        metrics_sink_parquet::ParquetSink,
+    },
+};
+
+/// type of the values of a metrics column
+#[derive(Clone, Copy)]
+pub enum MetricType {
+    Int,
+    Float,
+    Text,
+}
+
+/// a column of metrics rows, all sinks receive the same columns in the same order
+pub struct MetricColumn {
+    pub name: String,
+    pub metric_type: MetricType,
+}
+
+impl MetricColumn {
+    pub fn new(name: impl Into<String>, metric_type: MetricType) -> Self {
+        MetricColumn {
+            name: name.into(),
+            metric_type,
+        }
+    }
+}
+
+/// a value of a metrics row, its variant matches the MetricType of its column
+pub enum MetricValue {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl std::fmt::Display for MetricValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetricValue::Int(value) => value.fmt(f),
+            MetricValue::Float(value) => value.fmt(f),
+            MetricValue::Text(value) => value.fmt(f),
+        }
+    }
+}
+
+/// output of metrics rows, every row is written when metrics are collected
+/// and close is called once when the metrics runtime stops.
+pub trait MetricsSink: Send {
+    fn write_row(&mut self, row: &[MetricValue]) -> Result<(), SmartnessError>;
+
+    fn close(self: Box<Self>) -> Result<(), SmartnessError>;
+}
+
+/// create a sink writing into {file_path}.{extension}, parent dirs are created if needed...
+pub fn create_sink(
+    kind: MetricsSinkKind,
+    file_path: &str,
+    columns: &[MetricColumn],
+) -> Result<(String, Box<dyn MetricsSink>), SmartnessError> {
+    let file_path = format!("{}.{}", file_path, kind.extension());
+    let metrics_path = Path::new(&file_path);
+
+    if let Some(parent) = metrics_path.parent() {
+        fs::create_dir_all(parent).map_err(SmartnessError::MetricsParentPathCreateError)?;
+    }
+
+    let sink: Box<dyn MetricsSink> = match kind {
+        MetricsSinkKind::Csv => Box::new(CsvSink::new(metrics_path, columns)?),
+        MetricsSinkKind::Jsonl => Box::new(JsonLinesSink::new(metrics_path, columns)?),
+        MetricsSinkKind::Parquet => Box::new(ParquetSink::new(metrics_path, columns)?),
+    };
+
+    Ok((file_path, sink))
+}
@@ -0,0 +1,83 @@
+use std::{fs::File, io, path::Path};
+
+use csv::Writer;
+
+use crate::{
+    error::SmartnessError,
+    metrics::metrics_sink::{MetricColumn, MetricValue, MetricsSink},
+};
+
+/// writes metrics rows as csv with a header row, flushed after every row
+pub struct CsvSink {
+    writer: Writer<File>,
+}
+
+impl CsvSink {
+    pub fn new(path: &Path, columns: &[MetricColumn]) -> Result<Self, SmartnessError> {
+        let file = File::create(path).map_err(SmartnessError::MetricsFileCreateError)?;
+        let mut writer = Writer::from_writer(file);
+
+        writer
+            .write_record(columns.iter().map(|column| column.name.as_str()))
+            .map_err(SmartnessError::MetricsFileWriteHeadersError)?;
+
+        Ok(CsvSink { writer })
+    }
+}
+
+impl MetricsSink for CsvSink {
+    fn write_row(&mut self, row: &[MetricValue]) -> Result<(), SmartnessError> {
+        self.writer
+            .write_record(row.iter().map(MetricValue::to_string))
+            .map_err(|err| SmartnessError::MetricsFileWriteError(io::Error::from(err)))?;
+
+        self.writer
+            .flush()
+            .map_err(SmartnessError::MetricsFileWriteError)
+    }
+
+    fn close(mut self: Box<Self>) -> Result<(), SmartnessError> {
+        self.writer
+            .flush()
+            .map_err(SmartnessError::MetricsFileWriteError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+    use crate::metrics::metrics_sink::MetricType;
+
+    #[test]
+    fn rows_are_written_after_the_header() {
+        let path = env::temp_dir().join("smartness-sink-test.csv");
+        let columns = [
+            MetricColumn::new("timestamp", MetricType::Int),
+            MetricColumn::new("mean", MetricType::Float),
+            MetricColumn::new("phase", MetricType::Text),
+        ];
+
+        let mut sink = Box::new(CsvSink::new(&path, &columns).unwrap());
+        sink.write_row(&[
+            MetricValue::Int(1),
+            MetricValue::Float(2.5),
+            MetricValue::Text("warm, up".to_owned()),
+        ])
+        .unwrap();
+        sink.write_row(&[
+            MetricValue::Int(2),
+            MetricValue::Float(3.0),
+            MetricValue::Text("load".to_owned()),
+        ])
+        .unwrap();
+
+        // rows are flushed, so they are in the file before it is closed
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "timestamp,mean,phase\n1,2.5,\"warm, up\"\n2,3,load\n"
+        );
+        sink.close().unwrap();
+    }
+}
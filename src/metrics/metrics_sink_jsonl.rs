@@ -0,0 +1,113 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Serialize, Serializer, ser::SerializeMap};
+
+use crate::{
+    error::SmartnessError,
+    metrics::metrics_sink::{MetricColumn, MetricValue, MetricsSink},
+};
+
+/// writes every metrics row as a json object in its own line, keyed by column names
+pub struct JsonLinesSink {
+    writer: BufWriter<File>,
+    column_names: Vec<String>,
+}
+
+impl JsonLinesSink {
+    pub fn new(path: &Path, columns: &[MetricColumn]) -> Result<Self, SmartnessError> {
+        let file = File::create(path).map_err(SmartnessError::MetricsFileCreateError)?;
+
+        Ok(JsonLinesSink {
+            writer: BufWriter::new(file),
+            column_names: columns.iter().map(|column| column.name.clone()).collect(),
+        })
+    }
+}
+
+// a row serialized as a json object, keys keep the order of columns
+struct JsonRow<'a> {
+    column_names: &'a [String],
+    row: &'a [MetricValue],
+}
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.row.len()))?;
+        for (name, value) in self.column_names.iter().zip(self.row) {
+            match value {
+                MetricValue::Int(value) => map.serialize_entry(name, value)?,
+                // serde_json writes NaN and infinity as null
+                MetricValue::Float(value) => map.serialize_entry(name, value)?,
+                MetricValue::Text(value) => map.serialize_entry(name, value)?,
+            }
+        }
+        map.end()
+    }
+}
+
+impl MetricsSink for JsonLinesSink {
+    fn write_row(&mut self, row: &[MetricValue]) -> Result<(), SmartnessError> {
+        let json_row = JsonRow {
+            column_names: &self.column_names,
+            row,
+        };
+
+        serde_json::to_writer(&mut self.writer, &json_row)
+            .map_err(|err| SmartnessError::MetricsFileWriteError(err.into()))?;
+
+        self.writer
+            .write_all(b"\n")
+            .and_then(|_| self.writer.flush())
+            .map_err(SmartnessError::MetricsFileWriteError)
+    }
+
+    fn close(mut self: Box<Self>) -> Result<(), SmartnessError> {
+        self.writer
+            .flush()
+            .map_err(SmartnessError::MetricsFileWriteError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+    use crate::metrics::metrics_sink::MetricType;
+
+    #[test]
+    fn rows_are_json_objects_keyed_by_column() {
+        let path = env::temp_dir().join("smartness-sink-test.jsonl");
+        let columns = [
+            MetricColumn::new("timestamp", MetricType::Int),
+            MetricColumn::new("mean", MetricType::Float),
+            MetricColumn::new("phase", MetricType::Text),
+        ];
+
+        let mut sink = Box::new(JsonLinesSink::new(&path, &columns).unwrap());
+        sink.write_row(&[
+            MetricValue::Int(1),
+            MetricValue::Float(2.5),
+            MetricValue::Text("load".to_owned()),
+        ])
+        .unwrap();
+        sink.write_row(&[
+            MetricValue::Int(2),
+            MetricValue::Float(f64::NAN),
+            MetricValue::Text("\"cool\" down".to_owned()),
+        ])
+        .unwrap();
+
+        // rows are flushed, so they are in the file before it is closed
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\"timestamp\":1,\"mean\":2.5,\"phase\":\"load\"}\n\
+             {\"timestamp\":2,\"mean\":null,\"phase\":\"\\\"cool\\\" down\"}\n"
+        );
+        sink.close().unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+use std::{fs::File, path::Path, sync::Arc};
+
+use parquet::{
+    basic::{Compression, LogicalType, Repetition, Type as PhysicalType},
+    column::writer::ColumnWriter,
+    data_type::ByteArray,
+    errors::ParquetError,
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::types::Type,
+};
+
+use crate::{
+    error::SmartnessError,
+    metrics::metrics_sink::{MetricColumn, MetricType, MetricValue, MetricsSink},
+};
+
+// rows kept in memory before a row group is written, with the default metrics interval
+// a row group holds one minute of metrics...
+const ROW_GROUP_SIZE: usize = 60;
+
+// values of a column waiting to be written in the next row group
+enum ColumnBuffer {
+    Int(Vec<i64>),
+    Float(Vec<f64>),
+    Text(Vec<ByteArray>),
+}
+
+/// writes metrics rows as a parquet file with one typed column per metric,
+/// rows are buffered into row groups and the file is only readable after close.
+pub struct ParquetSink {
+    writer: SerializedFileWriter<File>,
+    buffers: Vec<ColumnBuffer>,
+    buffered_rows: usize,
+}
+
+impl ParquetSink {
+    pub fn new(path: &Path, columns: &[MetricColumn]) -> Result<Self, SmartnessError> {
+        let file = File::create(path).map_err(SmartnessError::MetricsFileCreateError)?;
+
+        let mut fields = Vec::with_capacity(columns.len());
+        let mut buffers = Vec::with_capacity(columns.len());
+        for column in columns {
+            let (physical_type, logical_type, buffer) = match column.metric_type {
+                MetricType::Int => (PhysicalType::INT64, None, ColumnBuffer::Int(Vec::new())),
+                MetricType::Float => (PhysicalType::DOUBLE, None, ColumnBuffer::Float(Vec::new())),
+                MetricType::Text => (
+                    PhysicalType::BYTE_ARRAY,
+                    Some(LogicalType::String),
+                    ColumnBuffer::Text(Vec::new()),
+                ),
+            };
+
+            let field = Type::primitive_type_builder(&column.name, physical_type)
+                .with_repetition(Repetition::REQUIRED)
+                .with_logical_type(logical_type)
+                .build()
+                .map_err(SmartnessError::MetricsParquetError)?;
+
+            fields.push(Arc::new(field));
+            buffers.push(buffer);
+        }
+
+        let schema = Type::group_type_builder("metrics")
+            .with_fields(fields)
+            .build()
+            .map_err(SmartnessError::MetricsParquetError)?;
+
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+
+        let writer = SerializedFileWriter::new(file, Arc::new(schema), Arc::new(properties))
+            .map_err(SmartnessError::MetricsParquetError)?;
+
+        Ok(ParquetSink {
+            writer,
+            buffers,
+            buffered_rows: 0,
+        })
+    }
+
+    fn write_row_group(&mut self) -> Result<(), ParquetError> {
+        if self.buffered_rows == 0 {
+            return Ok(());
+        }
+
+        let mut row_group = self.writer.next_row_group()?;
+        let mut buffers = self.buffers.iter_mut();
+        while let Some(mut column) = row_group.next_column()? {
+            match (column.untyped(), buffers.next()) {
+                (ColumnWriter::Int64ColumnWriter(writer), Some(ColumnBuffer::Int(values))) => {
+                    writer.write_batch(values, None, None)?;
+                    values.clear();
+                }
+                (ColumnWriter::DoubleColumnWriter(writer), Some(ColumnBuffer::Float(values))) => {
+                    writer.write_batch(values, None, None)?;
+                    values.clear();
+                }
+                (ColumnWriter::ByteArrayColumnWriter(writer), Some(ColumnBuffer::Text(values))) => {
+                    writer.write_batch(values, None, None)?;
+                    values.clear();
+                }
+                _ => {
+                    return Err(ParquetError::General(
+                        "metrics column does not match parquet schema".to_owned(),
+                    ));
+                }
+            }
+            column.close()?;
+        }
+        row_group.close()?;
+
+        self.buffered_rows = 0;
+        Ok(())
+    }
+}
+
+impl MetricsSink for ParquetSink {
+    fn write_row(&mut self, row: &[MetricValue]) -> Result<(), SmartnessError> {
+        // the whole row is checked first, so buffers always keep the same quantity of values
+        let row_matches = row.len() == self.buffers.len()
+            && self.buffers.iter().zip(row).all(|(buffer, value)| {
+                matches!(
+                    (buffer, value),
+                    (ColumnBuffer::Int(_), MetricValue::Int(_))
+                        | (ColumnBuffer::Float(_), MetricValue::Float(_))
+                        | (ColumnBuffer::Text(_), MetricValue::Text(_))
+                )
+            });
+
+        if !row_matches {
+            return Err(SmartnessError::MetricsParquetError(ParquetError::General(
+                "metrics row does not match parquet schema".to_owned(),
+            )));
+        }
+
+        for (buffer, value) in self.buffers.iter_mut().zip(row) {
+            match (buffer, value) {
+                (ColumnBuffer::Int(values), MetricValue::Int(value)) => values.push(*value),
+                (ColumnBuffer::Float(values), MetricValue::Float(value)) => values.push(*value),
+                (ColumnBuffer::Text(values), MetricValue::Text(value)) => {
+                    values.push(ByteArray::from(value.as_str()))
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        self.buffered_rows += 1;
+        if self.buffered_rows >= ROW_GROUP_SIZE {
+            self.write_row_group()
+                .map_err(SmartnessError::MetricsParquetError)?;
+        }
+
+        Ok(())
+    }
+
+    fn close(mut self: Box<Self>) -> Result<(), SmartnessError> {
+        self.write_row_group()
+            .map_err(SmartnessError::MetricsParquetError)?;
+
+        self.writer
+            .close()
+            .map(|_| ())
+            .map_err(SmartnessError::MetricsParquetError)
+    }
+}
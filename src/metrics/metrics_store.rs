@@ -1,81 +1,210 @@
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use hdrhistogram::Histogram;
 
 use crate::config::smarteness_settings::SmartnessSettings;
 
-const ORDER_TYPE: Ordering = Ordering::Relaxed;
+/// quantity of histograms used to slide the window, each one keeps latencies of a slot
+/// of time and the oldest one is reused when the window moves to a new slot
+const WINDOWED_SLOTS: u64 = 60;
 
 pub struct MetricsStore {
-    pub latency_vec: Vec<f64>,
-    pub drained_vec: Vec<f64>,
-    pub windowed_vec: Vec<f64>,
-    pub windowed_size: i32,
+    pub latency_histogram: Histogram<u64>,
+    pub drained_histogram: Histogram<u64>,
+    /// latencies since the last metrics row, reset when a row is written
+    pub interval_histogram: Histogram<u64>,
+    pub windowed_histograms: Vec<Histogram<u64>>,
+    /// slot of time kept by each windowed histogram, counted from start_time
+    pub windowed_slots: Vec<u64>,
+    /// duration of a slot in milliseconds, window size divided by WINDOWED_SLOTS
+    pub windowed_slot_millis: u64,
     pub drain_interval_min: u64,
     pub start_time: std::time::Instant,
-    pub last_tick: AtomicU64,
+    /// second, counted from start_time, when the current drain interval started
+    pub last_tick: u64,
 }
 
 impl MetricsStore {
     pub fn new(smartness_settings: &SmartnessSettings) -> Self {
         let now = std::time::Instant::now();
+        let precision = smartness_settings.metrics_histogram_precision.unwrap();
+        let windowed_size_secs = smartness_settings.metrics_window_size.unwrap() as u64;
 
         MetricsStore {
-            latency_vec: Vec::new(),
-            drained_vec: Vec::new(),
-            windowed_vec: Vec::with_capacity(
-                smartness_settings.metrics_window_size.unwrap() as usize
-            ),
-            windowed_size: smartness_settings.metrics_window_size.unwrap(),
+            latency_histogram: new_histogram(precision),
+            drained_histogram: new_histogram(precision),
+            interval_histogram: new_histogram(precision),
+            windowed_histograms: (0..WINDOWED_SLOTS)
+                .map(|_| new_histogram(precision))
+                .collect(),
+            windowed_slots: vec![0; WINDOWED_SLOTS as usize],
+            windowed_slot_millis: (windowed_size_secs * 1_000 / WINDOWED_SLOTS).max(1),
             drain_interval_min: smartness_settings.metrics_drain_interval_minutes.unwrap() as u64,
             start_time: now,
-            last_tick: AtomicU64::new(now.elapsed().as_secs() as u64),
+            last_tick: 0,
         }
     }
 
-    pub fn add_latency(&mut self, latency: f64, old_count: u64) {
-        self.latency_vec.push(latency);
-        self.add_to_drainec_vec(latency);
-        self.add_to_windowed_vec(latency, old_count);
+    /// add latencies merged from recorder shards, they are placed in time when they are merged...
+    pub fn add_histogram(&mut self, histogram: &Histogram<u64>) {
+        self.add_histogram_at(histogram, self.start_time.elapsed());
     }
 
-    pub fn get_latency_vec_clone(&self) -> Vec<f64> {
-        self.latency_vec.clone()
+    /// add latencies as if they were merged `elapsed` time after start_time
+    pub fn add_histogram_at(&mut self, histogram: &Histogram<u64>, elapsed: Duration) {
+        // histograms are auto resizable, so addition does not fail...
+        let _ = self.latency_histogram.add(histogram);
+        self.add_to_drained_histogram(histogram, elapsed);
+        let _ = self.interval_histogram.add(histogram);
+        self.add_to_windowed_histograms(histogram, elapsed);
     }
 
-    pub fn get_drained_vec_clone(&self) -> Vec<f64> {
-        self.drained_vec.clone()
+    pub fn get_latency_histogram(&self) -> &Histogram<u64> {
+        &self.latency_histogram
     }
 
-    pub fn get_windowed_vec_clone(&self) -> Vec<f64> {
-        self.windowed_vec.clone()
+    pub fn get_drained_histogram(&self) -> &Histogram<u64> {
+        &self.drained_histogram
     }
 
-    fn add_to_drainec_vec(&mut self, latency: f64) {
-        // Multiple threads could read the same `old_tick`...
-        let old_tick = self.last_tick.load(ORDER_TYPE);
-        let new_tick = self.start_time.elapsed().as_secs() as u64;
-        let elapsed = new_tick - old_tick;
+    pub fn get_interval_histogram(&self) -> &Histogram<u64> {
+        &self.interval_histogram
+    }
 
-        if elapsed > self.drain_interval_min * 60 {
-            let new_interval_start_tick = new_tick - elapsed % (self.drain_interval_min * 60);
-            // But then only one will succeed in the following COMPARE EXCHANGE operation.
-            if self
-                .last_tick
-                .compare_exchange(old_tick, new_interval_start_tick, ORDER_TYPE, ORDER_TYPE)
-                .is_ok()
-            {
-                self.drained_vec.clear();
+    pub fn reset_interval_histogram(&mut self) {
+        self.interval_histogram.reset();
+    }
+
+    /// merge slots inside the window into a single histogram...
+    /// slots older than the window are skipped, so latencies leave the window even without traffic.
+    pub fn get_windowed_histogram(&self) -> Histogram<u64> {
+        self.get_windowed_histogram_at(self.start_time.elapsed())
+    }
+
+    /// window as it is read `elapsed` time after start_time
+    pub fn get_windowed_histogram_at(&self, elapsed: Duration) -> Histogram<u64> {
+        let current_slot = self.windowed_slot(elapsed);
+        let mut windowed_histogram = self.windowed_histograms[0].clone();
+        windowed_histogram.reset();
+
+        for (slot, histogram) in self.windowed_slots.iter().zip(&self.windowed_histograms) {
+            if current_slot - slot < WINDOWED_SLOTS {
+                // histograms are auto resizable, so addition does not fail...
+                let _ = windowed_histogram.add(histogram);
             }
         }
+        windowed_histogram
+    }
+
+    fn windowed_slot(&self, elapsed: Duration) -> u64 {
+        elapsed.as_millis() as u64 / self.windowed_slot_millis
+    }
+
+    fn add_to_drained_histogram(&mut self, histogram: &Histogram<u64>, elapsed: Duration) {
+        // drained latencies are reset when a drain interval is over,
+        // the next interval starts at the last multiple of drain interval...
+        let new_tick = elapsed.as_secs();
+        let elapsed = new_tick - self.last_tick;
+
+        if elapsed > self.drain_interval_min * 60 {
+            self.last_tick = new_tick - elapsed % (self.drain_interval_min * 60);
+            self.drained_histogram.reset();
+        }
 
-        self.drained_vec.push(latency);
+        let _ = self.drained_histogram.add(histogram);
     }
 
-    fn add_to_windowed_vec(&mut self, latency: f64, count: u64) {
-        let position = count % self.windowed_size as u64;
-        if count < self.windowed_size as u64 {
-            self.windowed_vec.push(latency);
-        } else {
-            self.windowed_vec[position as usize] = latency;
+    fn add_to_windowed_histograms(&mut self, histogram: &Histogram<u64>, elapsed: Duration) {
+        // window keeps latencies of the last windowed_size seconds, sliding one slot at a time...
+        let current_slot = self.windowed_slot(elapsed);
+        let position = (current_slot % WINDOWED_SLOTS) as usize;
+        let slot_histogram = &mut self.windowed_histograms[position];
+        if self.windowed_slots[position] != current_slot {
+            slot_histogram.reset();
+            self.windowed_slots[position] = current_slot;
         }
+        let _ = slot_histogram.add(histogram);
+    }
+}
+
+pub fn new_histogram(precision: u8) -> Histogram<u64> {
+    // precision is validated by SmartnessSettings...
+    Histogram::new(precision).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::config::smarteness_settings::test_settings;
+
+    use super::*;
+
+    // window of 60 seconds, so each of the 60 slots keeps one second
+    fn windowed_store(test_name: &str) -> MetricsStore {
+        MetricsStore::new(&test_settings(
+            test_name,
+            json!({ "metrics_window_size_minutes": 1 }),
+        ))
+    }
+
+    fn latency(value: u64) -> Histogram<u64> {
+        let mut histogram = new_histogram(3);
+        histogram.record(value).unwrap();
+        histogram
+    }
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn latencies_leave_the_window_after_its_size() {
+        let mut store = windowed_store("window-leave");
+        store.add_histogram_at(&latency(100), secs(0.5));
+        store.add_histogram_at(&latency(200), secs(30.5));
+
+        assert_eq!(store.get_windowed_histogram_at(secs(59.5)).len(), 2);
+
+        // the first latency leaves the window once its slot is older than 60 seconds...
+        let windowed_histogram = store.get_windowed_histogram_at(secs(60.5));
+        assert_eq!(windowed_histogram.len(), 1);
+        assert_eq!(windowed_histogram.min(), 200);
+
+        // and the window is empty without traffic, while the total keeps everything
+        assert!(store.get_windowed_histogram_at(secs(90.5)).is_empty());
+        assert_eq!(store.get_latency_histogram().len(), 2);
+    }
+
+    #[test]
+    fn slots_are_reused_when_the_window_slides() {
+        let mut store = windowed_store("window-reuse");
+        store.add_histogram_at(&latency(100), secs(5.5));
+        store.add_histogram_at(&latency(150), secs(5.9));
+        assert_eq!(store.windowed_histograms[5].len(), 2);
+
+        // one window later the same slot is reset before it keeps the new latency
+        store.add_histogram_at(&latency(300), secs(65.5));
+        assert_eq!(store.windowed_slots[5], 65);
+        assert_eq!(store.windowed_histograms[5].len(), 1);
+
+        let windowed_histogram = store.get_windowed_histogram_at(secs(65.5));
+        assert_eq!(windowed_histogram.len(), 1);
+        assert_eq!(windowed_histogram.min(), 300);
+    }
+
+    #[test]
+    fn slot_size_follows_the_window_size() {
+        let mut store = MetricsStore::new(&test_settings(
+            "window-slot-size",
+            json!({ "metrics_window_size_minutes": 2 }),
+        ));
+        assert_eq!(store.windowed_slot_millis, 2_000);
+
+        store.add_histogram_at(&latency(100), secs(1.0));
+        store.add_histogram_at(&latency(200), secs(1.9));
+        assert_eq!(store.windowed_histograms[0].len(), 2);
+        assert_eq!(store.get_windowed_histogram_at(secs(119.9)).len(), 2);
+        assert!(store.get_windowed_histogram_at(secs(120.0)).is_empty());
     }
 }
@@ -1,2 +1,11 @@
+pub mod metrics_error;
 pub mod metrics_manager;
+pub mod metrics_prometheus;
+pub mod metrics_rate;
+pub mod metrics_recorder;
+pub mod metrics_report;
+pub mod metrics_sink;
+pub mod metrics_sink_csv;
+pub mod metrics_sink_jsonl;
+pub mod metrics_sink_parquet;
 pub mod metrics_store;